@@ -0,0 +1,98 @@
+//! Benchmarks of the simulation step, the full image redraw, the lighting pass and the per-cell color variation.
+//! Everything runs without a window: `cargo bench --bench simulation`
+//!
+//! The avalanching and settled grids are the start and end of the `avalanche` scenario of the golden-image tests
+
+use std::{hint::black_box, path::Path};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sandfall_mimimi::{game::sandworld::{draw_image::{draw_grid_cells, GridStyle}, image_setup::new_grid_image, lighting::{apply_lighting, LightMap, MAX_LIGHT}, main_interaction::simulation_step, scenario::Scenario, wall_layer::WallCells, Elem, ElemKind, ElemPos, GridCells, SandColor, GRID_SIZE}, settings::{ColorPalette, LightingMode}};
+
+const HALF_FULL_SEED: u64 = 0x5A4D;
+
+fn load_scenario(name: &str) -> Scenario {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scenarios").join(format!("{name}.txt"));
+    Scenario::load(&path).unwrap_or_else(|err| panic!("{}: {err}", path.display()))
+}
+
+/// Every other cell on average holds a random non-empty kind, always the same for a given seed
+fn half_full_grid(seed: u64) -> GridCells {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut grid_cells = GridCells::new_empty();
+    for y in 0..GRID_SIZE.height {
+        for x in 0..GRID_SIZE.width {
+            if rng.random_bool(0.5) {
+                let kind = ElemKind::ALL[rng.random_range(1..ElemKind::ALL.len())];
+                grid_cells.set_elem_at(ElemPos::new(x, y), Elem::new(kind, false));
+            }
+        }
+    }
+    grid_cells
+}
+
+fn bench_simulation_step(c: &mut Criterion) {
+    let avalanche = load_scenario("avalanche");
+    let grids = [
+        ("empty", GridCells::new_empty()),
+        ("half_full", half_full_grid(HALF_FULL_SEED)),
+        ("avalanching", avalanche.grid.clone()),
+        ("settled", avalanche.run()),
+    ];
+
+    let mut group = c.benchmark_group("main_interaction_loop");
+    for (name, grid_cells) in grids {
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || grid_cells.clone(),
+                |grid_cells| simulation_step(grid_cells, 0),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_draw_image(c: &mut Criterion) {
+    let wall_cells = WallCells::default();
+    let mut group = c.benchmark_group("draw_image");
+    for (name, grid_cells) in [("empty", GridCells::new_empty()), ("half_full", half_full_grid(HALF_FULL_SEED))] {
+        let mut image = new_grid_image(GRID_SIZE);
+        group.bench_function(name, |b| b.iter(|| draw_grid_cells(black_box(&grid_cells), &wall_cells, &mut image, GridStyle::default(), 0)));
+    }
+    group.finish();
+}
+
+/// Light map and lighting pass of a half full grid lit by a row of emitters, no element emits light yet
+fn bench_lighting(c: &mut Criterion) {
+    let grid_cells = half_full_grid(HALF_FULL_SEED);
+    let sources: Vec<(ElemPos, u8)> = (0..GRID_SIZE.width).step_by(16).map(|x| (ElemPos::new(x, GRID_SIZE.height / 2), MAX_LIGHT)).collect();
+    let light_map = LightMap::from_sources(&grid_cells, sources.iter().copied());
+    let mut image = new_grid_image(GRID_SIZE);
+    draw_grid_cells(&grid_cells, &WallCells::default(), &mut image, GridStyle::default(), 0);
+
+    let mut group = c.benchmark_group("lighting");
+    group.bench_function("light_map", |b| b.iter(|| LightMap::from_sources(black_box(&grid_cells), sources.iter().copied())));
+    for mode in [LightingMode::Lit, LightingMode::Glow] {
+        group.bench_function(format!("{mode:?}").to_lowercase(), |b| {
+            b.iter_batched_ref(
+                || image.clone(),
+                |image| apply_lighting(image, &light_map, mode),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_elem_color(c: &mut Criterion) {
+    let mut group = c.benchmark_group("elem_color");
+    for kind in [ElemKind::Empty, ElemKind::Stone, ElemKind::Sand(SandColor::Yellow)] {
+        let pos = ElemPos::new(GRID_SIZE.width / 2, GRID_SIZE.height / 2);
+        let elem = Elem::new(kind, false).seeded_at(pos);
+        group.bench_function(kind.name(), |b| b.iter(|| black_box(elem).color(black_box(pos), 0, ColorPalette::Standard)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_simulation_step, bench_draw_image, bench_lighting, bench_elem_color);
+criterion_main!(benches);
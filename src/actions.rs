@@ -0,0 +1,324 @@
+use std::{collections::{BTreeMap, HashMap}, fmt::Display};
+use bevy::{app::{Plugin, Startup, Update}, ecs::{change_detection::DetectChanges, resource::Resource, system::{Query, Res, ResMut, SystemParam}}, input::{gamepad::{Gamepad, GamepadButton}, keyboard::KeyCode, mouse::MouseButton, ButtonInput}, log::warn};
+use crate::{game::sandworld::debug_overlay::OVERLAY_KEYS, settings::Settings};
+
+/// Everything the player can do with a single key or mouse button
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum Action {
+    PaintPrimary,
+    Erase,
+    SelectNextElement,
+    SelectPreviousElement,
+    ResetBrushRadius,
+    ToggleWallLayer,
+    PanCamera,
+    FitCamera,
+    Pause,
+    ToggleFullscreen,
+    PauseSimulation,
+    StepSimulation,
+    SpeedUp,
+    SlowDown,
+    Screenshot,
+    ToggleRecording,
+    ToggleReplayRecording,
+    PlayReplay,
+}
+impl Action {
+    pub const ALL: [Action; 18] = [
+        Action::PaintPrimary,
+        Action::Erase,
+        Action::SelectNextElement,
+        Action::SelectPreviousElement,
+        Action::ResetBrushRadius,
+        Action::ToggleWallLayer,
+        Action::PanCamera,
+        Action::FitCamera,
+        Action::Pause,
+        Action::ToggleFullscreen,
+        Action::PauseSimulation,
+        Action::StepSimulation,
+        Action::SpeedUp,
+        Action::SlowDown,
+        Action::Screenshot,
+        Action::ToggleRecording,
+        Action::ToggleReplayRecording,
+        Action::PlayReplay,
+    ];
+
+    pub fn default_binding(&self) -> Binding {
+        match self {
+            Action::PaintPrimary => Binding::Mouse(MouseButton::Left),
+            Action::Erase => Binding::Mouse(MouseButton::Right),
+            Action::SelectNextElement => Binding::Key(KeyCode::KeyM),
+            Action::SelectPreviousElement => Binding::Key(KeyCode::KeyB),
+            Action::ResetBrushRadius => Binding::Key(KeyCode::KeyN),
+            Action::ToggleWallLayer => Binding::Key(KeyCode::KeyL),
+            Action::PanCamera => Binding::Mouse(MouseButton::Middle),
+            Action::FitCamera => Binding::Key(KeyCode::KeyF),
+            Action::Pause => Binding::Key(KeyCode::Escape),
+            Action::ToggleFullscreen => Binding::Key(KeyCode::F11),
+            Action::PauseSimulation => Binding::Key(KeyCode::Space),
+            Action::StepSimulation => Binding::Key(KeyCode::Period),
+            Action::SpeedUp => Binding::Key(KeyCode::Equal),
+            Action::SlowDown => Binding::Key(KeyCode::Minus),
+            Action::Screenshot => Binding::Key(KeyCode::F12),
+            Action::ToggleRecording => Binding::Key(KeyCode::F9),
+            Action::ToggleReplayRecording => Binding::Key(KeyCode::F6),
+            Action::PlayReplay => Binding::Key(KeyCode::F7),
+        }
+    }
+
+    /// Button of a standard gamepad layout that also triggers the action. Unlike keys and mouse buttons these
+    /// cannot be rebound. The left stick moves the painting cursor, see [`VirtualCursor`](crate::game::gamepad_cursor::VirtualCursor)
+    pub fn gamepad_button(&self) -> Option<GamepadButton> {
+        match self {
+            Action::PaintPrimary => Some(GamepadButton::South),
+            Action::Erase => Some(GamepadButton::West),
+            Action::SelectNextElement => Some(GamepadButton::RightTrigger),
+            Action::SelectPreviousElement => Some(GamepadButton::LeftTrigger),
+            Action::ResetBrushRadius => Some(GamepadButton::DPadDown),
+            Action::FitCamera => Some(GamepadButton::North),
+            Action::Pause => Some(GamepadButton::Start),
+            Action::PauseSimulation => Some(GamepadButton::Select),
+            Action::StepSimulation => Some(GamepadButton::DPadUp),
+            Action::SpeedUp => Some(GamepadButton::DPadRight),
+            Action::SlowDown => Some(GamepadButton::DPadLeft),
+            _ => None,
+        }
+    }
+
+    /// Identifier used as the key in the settings file
+    pub fn name(&self) -> String {
+        format!("{self:?}")
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    /// Human readable name shown in the controls menu
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::PaintPrimary => "Paint",
+            Action::Erase => "Erase",
+            Action::SelectNextElement => "Next element",
+            Action::SelectPreviousElement => "Previous element",
+            Action::ResetBrushRadius => "Reset brush",
+            Action::ToggleWallLayer => "Wall layer",
+            Action::PanCamera => "Pan camera",
+            Action::FitCamera => "Fit camera",
+            Action::Pause => "Pause menu",
+            Action::ToggleFullscreen => "Fullscreen",
+            Action::PauseSimulation => "Pause simulation",
+            Action::StepSimulation => "Step simulation",
+            Action::SpeedUp => "Speed up",
+            Action::SlowDown => "Slow down",
+            Action::Screenshot => "Screenshot",
+            Action::ToggleRecording => "Record GIF",
+            Action::ToggleReplayRecording => "Record replay",
+            Action::PlayReplay => "Play replay",
+        }
+    }
+}
+
+/// Keys that can be bound to an [`Action`], in the order they are looked up by name
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF, KeyCode::KeyG,
+    KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL, KeyCode::KeyM, KeyCode::KeyN,
+    KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR, KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU,
+    KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX, KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+    KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::Escape, KeyCode::Space, KeyCode::Enter, KeyCode::Tab, KeyCode::Backspace,
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight,
+    KeyCode::Home, KeyCode::End, KeyCode::PageUp, KeyCode::PageDown, KeyCode::Insert, KeyCode::Delete,
+    KeyCode::Minus, KeyCode::Equal, KeyCode::Period, KeyCode::Comma, KeyCode::Slash, KeyCode::Backslash,
+    KeyCode::Semicolon, KeyCode::Quote, KeyCode::BracketLeft, KeyCode::BracketRight, KeyCode::Backquote,
+    KeyCode::NumpadAdd, KeyCode::NumpadSubtract, KeyCode::NumpadMultiply, KeyCode::NumpadDivide,
+    KeyCode::NumpadEnter, KeyCode::NumpadDecimal, KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2,
+];
+const BINDABLE_MOUSE_BUTTONS: &[MouseButton] = &[
+    MouseButton::Left, MouseButton::Right, MouseButton::Middle, MouseButton::Back, MouseButton::Forward,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+impl Binding {
+    /// Parses the names written by the [`Display`] implementation, such as `KeyM`, `F11` or `MouseLeft`
+    pub fn from_name(name: &str) -> Option<Self> {
+        BINDABLE_KEYS.iter().copied().map(Binding::Key)
+            .chain(BINDABLE_MOUSE_BUTTONS.iter().copied().map(Binding::Mouse))
+            .find(|binding| binding.to_string() == name)
+    }
+
+    /// Keys with a fixed use elsewhere, such as the [`OVERLAY_KEYS`], which no action can be bound to
+    pub fn is_reserved(&self) -> bool {
+        matches!(self, Binding::Key(key) if OVERLAY_KEYS.contains(key))
+    }
+
+    /// The first bindable key or mouse button pressed this frame
+    pub fn just_pressed(keys: &ButtonInput<KeyCode>, mouse: &ButtonInput<MouseButton>) -> Option<Self> {
+        BINDABLE_KEYS.iter().copied().find(|key| keys.just_pressed(*key)).map(Binding::Key)
+            .or_else(|| BINDABLE_MOUSE_BUTTONS.iter().copied().find(|button| mouse.just_pressed(*button)).map(Binding::Mouse))
+    }
+}
+impl Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key:?}"),
+            Binding::Mouse(button) => write!(f, "Mouse{button:?}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RebindError {
+    Reserved(Binding),
+}
+impl Display for RebindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RebindError::Reserved(binding) => write!(f, "{binding} is reserved and cannot be bound"),
+        }
+    }
+}
+impl std::error::Error for RebindError {}
+
+/// The binding of every [`Action`], loaded from and saved to the key bindings of the [`Settings`]
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct ActionMap {
+    bindings: HashMap<Action, Binding>,
+}
+impl Default for ActionMap {
+    fn default() -> Self {
+        ActionMap { bindings: Action::ALL.into_iter().map(|action| (action, action.default_binding())).collect() }
+    }
+}
+impl ActionMap {
+    /// Applies the stored bindings over the defaults. Unknown names and reserved keys are skipped, and when two actions share a
+    /// binding the later one in [`Action::ALL`] falls back to its default, or the earlier one if the later one
+    /// already has it. This repeats until every action has a binding of its own
+    pub fn from_settings(stored: &BTreeMap<String, String>) -> Self {
+        let mut map = ActionMap::default();
+        for (action_name, binding_name) in stored {
+            match (Action::from_name(action_name), Binding::from_name(binding_name)) {
+                (Some(_), Some(binding)) if binding.is_reserved() => warn!("Ignoring key binding {action_name} = {binding_name}, {binding} is reserved"),
+                (Some(action), Some(binding)) => map.bind(action, binding),
+                _ => warn!("Ignoring unknown key binding {action_name} = {binding_name}"),
+            }
+        }
+
+        while let Some((earlier, later)) = map.first_conflict() {
+            // The defaults never conflict, so at least one of the two was rebound
+            let Some(reset) = [later, earlier].into_iter().find(|action| map.get(*action) != action.default_binding()) else { break };
+            warn!("{} and {} are both bound to {}, {} is reset to its default", earlier.name(), later.name(), map.get(later), reset.name());
+            map.bind(reset, reset.default_binding());
+        }
+        map
+    }
+
+    /// Only the bindings that differ from the defaults, as stored in the settings
+    pub fn to_settings(&self) -> BTreeMap<String, String> {
+        Action::ALL.into_iter()
+            .filter(|action| self.get(*action) != action.default_binding())
+            .map(|action| (action.name(), self.get(action).to_string()))
+            .collect()
+    }
+
+    pub fn get(&self, action: Action) -> Binding {
+        self.bindings.get(&action).copied().unwrap_or_else(|| action.default_binding())
+    }
+
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        self.bindings.insert(action, binding);
+    }
+
+    /// Another action already using the binding
+    pub fn conflict(&self, action: Action, binding: Binding) -> Option<Action> {
+        Action::ALL.into_iter().find(|other| *other != action && self.get(*other) == binding)
+    }
+
+    /// The first two actions of [`Action::ALL`] sharing a binding, in that order
+    fn first_conflict(&self) -> Option<(Action, Action)> {
+        Action::ALL.into_iter().enumerate().find_map(|(i, later)| {
+            Action::ALL[..i].iter().find(|earlier| self.get(**earlier) == self.get(later)).map(|earlier| (*earlier, later))
+        })
+    }
+
+    /// Binds the action, giving its previous binding to the action that used the new one. Returns that action,
+    /// or an error leaving every binding as it was if the binding is [reserved](Binding::is_reserved)
+    pub fn rebind_swapping(&mut self, action: Action, binding: Binding) -> Result<Option<Action>, RebindError> {
+        if binding.is_reserved() {
+            return Err(RebindError::Reserved(binding))
+        }
+        let previous = self.get(action);
+        let conflict = self.conflict(action, binding);
+        if let Some(other) = conflict {
+            self.bind(other, previous);
+        }
+        self.bind(action, binding);
+        Ok(conflict)
+    }
+}
+
+/// Reads the state of [`Action`]s through the [`ActionMap`] instead of raw keys and mouse buttons,
+/// together with the [`Action::gamepad_button`] of every connected gamepad
+#[derive(SystemParam)]
+pub struct ActionInput<'w, 's> {
+    map: Res<'w, ActionMap>,
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    gamepads: Query<'w, 's, &'static Gamepad>,
+}
+impl ActionInput<'_, '_> {
+    pub fn pressed(&self, action: Action) -> bool {
+        let bound = match self.map.get(action) {
+            Binding::Key(key) => self.keys.pressed(key),
+            Binding::Mouse(button) => self.mouse.pressed(button),
+        };
+        bound || action.gamepad_button().is_some_and(|button| self.gamepads.iter().any(|gamepad| gamepad.pressed(button)))
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        let bound = match self.map.get(action) {
+            Binding::Key(key) => self.keys.just_pressed(key),
+            Binding::Mouse(button) => self.mouse.just_pressed(button),
+        };
+        bound || action.gamepad_button().is_some_and(|button| self.gamepads.iter().any(|gamepad| gamepad.just_pressed(button)))
+    }
+
+    /// Modifiers stay fixed keys, they only change what some actions do
+    pub fn shift(&self) -> bool {
+        self.keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    }
+}
+
+/// Loads the [`ActionMap`] from the [`Settings`] and stores it back whenever it is rebound
+pub struct ActionsPlugin;
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut bevy::app::App) {
+        app
+        .init_resource::<ActionMap>()
+        .add_systems(Startup, load_action_map)
+        .add_systems(Update, store_action_map);
+    }
+}
+
+fn load_action_map(settings: Res<Settings>, mut map: ResMut<ActionMap>) {
+    *map = ActionMap::from_settings(&settings.key_bindings);
+}
+
+fn store_action_map(map: Res<ActionMap>, mut settings: ResMut<Settings>) {
+    if !map.is_changed() || map.is_added() { return }
+
+    let stored = map.to_settings();
+    if settings.key_bindings != stored {
+        settings.key_bindings = stored;
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+use bevy::{asset::{Assets, RenderAssetUsages}, color::{Color, ColorToPacked, Mix}, core_pipeline::core_2d::Camera2d, ecs::{change_detection::DetectChanges, component::Component, query::{With, Without}, system::{Commands, Local, Res, ResMut, Single}}, image::Image, log::warn, render::render_resource::{Extent3d, TextureDimension, TextureFormat}, sprite::Sprite, transform::components::Transform};
+use image::{ImageReader, ImageResult, RgbaImage};
+use crate::{game::{camera::grid_world_size, sandworld::GridSize}, settings::{BackgroundMode, Settings}};
+
+/// Bevy's default clear colour, which was the background before it could be configured
+const SOLID_COLOR: Color = Color::srgb(0.169, 0.173, 0.184);
+const GRADIENT_TOP: Color = Color::srgb(0.32, 0.45, 0.62);
+const GRADIENT_BOTTOM: Color = Color::srgb(0.10, 0.10, 0.16);
+/// Rows of the gradient texture, enough for the nearest sampler not to show bands
+const GRADIENT_ROWS: u32 = 512;
+/// Size of the background relative to the grid, large enough to fill the view at the furthest zoom and pan
+const BACKGROUND_SCALE: f32 = 3.;
+/// Share of the camera movement the background follows, it seems further away the closer this is to 1
+const PARALLAX: f32 = 0.5;
+/// Behind the grid sprite, which lies at 0
+const BACKGROUND_Z: f32 = -1.;
+
+/// The sprite drawn behind the transparent cells of the grid, see [`BackgroundMode`]
+#[derive(Component)]
+pub struct BackgroundLayer;
+
+pub fn spawn_background(mut commands: Commands, size: Res<GridSize>) {
+    commands.spawn((
+        Sprite::from_color(SOLID_COLOR, grid_world_size(*size) * BACKGROUND_SCALE),
+        Transform::from_xyz(0., 0., BACKGROUND_Z),
+        BackgroundLayer,
+    ));
+}
+
+/// Redraws the background whenever its mode or image change, or a world of another size starts. Other settings
+/// such as the window size that change while resizing leave it alone. An image that cannot be read falls back to the gradient
+pub fn update_background(
+    settings: Res<Settings>,
+    grid_size: Res<GridSize>,
+    mut images: ResMut<Assets<Image>>,
+    mut background: Single<&mut Sprite, With<BackgroundLayer>>,
+    mut drawn: Local<Option<(BackgroundMode, String, GridSize)>>,
+) {
+    if !settings.is_changed() && !grid_size.is_changed() { return }
+    let wanted = (settings.background, settings.background_image.clone(), *grid_size);
+    if drawn.as_ref() == Some(&wanted) { return }
+    *drawn = Some(wanted);
+
+    let size = grid_world_size(*grid_size) * BACKGROUND_SCALE;
+    let image = match settings.background {
+        BackgroundMode::Solid => None,
+        BackgroundMode::Gradient => Some(gradient_image()),
+        BackgroundMode::Image => match read_rgba(Path::new(&settings.background_image)) {
+            Ok(rgba) => Some(rgba_to_image(rgba)),
+            Err(err) => {
+                warn!("Could not load the background image {:?}: {err}", settings.background_image);
+                Some(gradient_image())
+            }
+        },
+    };
+
+    **background = match image {
+        Some(image) => Sprite { custom_size: Some(size), ..Sprite::from_image(images.add(image)) },
+        None => Sprite::from_color(SOLID_COLOR, size),
+    };
+}
+
+/// Moves the background along with part of the camera movement, so it scrolls slower than the grid
+pub fn parallax_background(
+    camera: Single<&Transform, With<Camera2d>>,
+    mut background: Single<&mut Transform, (With<BackgroundLayer>, Without<Camera2d>)>,
+) {
+    let offset = camera.translation.truncate() * PARALLAX;
+    background.translation.x = offset.x;
+    background.translation.y = offset.y;
+}
+
+fn read_rgba(path: &Path) -> ImageResult<RgbaImage> {
+    Ok(ImageReader::open(path)?.with_guessed_format()?.decode()?.to_rgba8())
+}
+
+/// Vertical gradient one pixel wide, stretched over the background
+fn gradient_image() -> Image {
+    let rgba = RgbaImage::from_fn(1, GRADIENT_ROWS, |_, y| {
+        let color = GRADIENT_TOP.mix(&GRADIENT_BOTTOM, y as f32 / (GRADIENT_ROWS - 1) as f32);
+        image::Rgba(color.to_srgba().to_u8_array())
+    });
+    rgba_to_image(rgba)
+}
+
+fn rgba_to_image(rgba: RgbaImage) -> Image {
+    Image::new(
+        Extent3d { width: rgba.width(), height: rgba.height(), depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        rgba.into_raw(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    )
+}
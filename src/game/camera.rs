@@ -0,0 +1,132 @@
+use bevy::{core_pipeline::core_2d::Camera2d, ecs::{event::EventReader, query::With, system::{Local, Res, Single}}, input::mouse::{AccumulatedMouseScroll, MouseScrollUnit}, log::error, math::Vec2, render::camera::Projection, transform::components::Transform, window::{PrimaryWindow, Window, WindowResized}};
+use crate::{actions::{Action, ActionInput}, game::sandworld::{GridSize, GRID_SCALE}};
+
+/// Scale change of one mouse wheel line
+const ZOOM_STEP: f32 = 1.15;
+/// Pixel scrolling (touchpads) is converted to wheel lines with this ratio
+const PIXELS_PER_LINE: f32 = 100.;
+/// How far the camera can zoom in and out, relative to the scale that fits the grid in the window
+const MIN_ZOOM: f32 = 1. / 32.;
+const MAX_ZOOM: f32 = 2.;
+
+/// Size of the sprite of a grid of `size` in world units, it is spawned centred on the origin
+pub fn grid_world_size(size: GridSize) -> Vec2 {
+    Vec2::new(size.width as f32, size.height as f32) * GRID_SCALE
+}
+
+/// Orthographic scale at which the whole grid is visible in the window, every cell covering the same whole number
+/// of physical pixels. The rest of the window is left as a letterbox around the grid
+pub fn fit_scale(window: &Window, size: GridSize) -> f32 {
+    let pixels_per_cell = (window.physical_width() / size.width)
+        .min(window.physical_height() / size.height)
+        .max(1);
+    GRID_SCALE * window.scale_factor() / pixels_per_cell as f32
+}
+
+/// Keeps the centre of the view over the grid, so the grid can never be scrolled off-screen
+fn clamp_to_grid(transform: &mut Transform, size: GridSize) {
+    let half = grid_world_size(size) / 2.;
+    transform.translation.x = transform.translation.x.clamp(-half.x, half.x);
+    transform.translation.y = transform.translation.y.clamp(-half.y, half.y);
+}
+
+/// Cursor position relative to the window centre, in logical pixels with y pointing up like the world
+fn cursor_from_center(window: &Window) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    let offset = cursor - window.size() / 2.;
+    Some(Vec2::new(offset.x, -offset.y))
+}
+
+/// The mouse wheel zooms in and out, keeping the world position under the cursor in place
+pub fn zoom_camera(
+    scroll: Res<AccumulatedMouseScroll>,
+    size: Res<GridSize>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&mut Transform, &mut Projection), With<Camera2d>>,
+) {
+    if scroll.delta.y == 0. { return }
+
+    let (mut transform, mut projection) = camera.into_inner();
+    let Projection::Orthographic(ortho) = projection.as_mut() else {
+        error!("Zooming Error: Projection is not Orthograpic as should be by Default");
+        return
+    };
+
+    let lines = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y,
+        MouseScrollUnit::Pixel => scroll.delta.y / PIXELS_PER_LINE,
+    };
+    let fit = fit_scale(&window, *size);
+    let old_scale = ortho.scale;
+    let new_scale = (old_scale * ZOOM_STEP.powf(-lines)).clamp(fit * MIN_ZOOM, fit * MAX_ZOOM);
+
+    if let Some(offset) = cursor_from_center(&window) {
+        let under_cursor = transform.translation.truncate() + offset * old_scale;
+        let centre = under_cursor - offset * new_scale;
+        transform.translation.x = centre.x;
+        transform.translation.y = centre.y;
+    }
+    ortho.scale = new_scale;
+    clamp_to_grid(&mut transform, *size);
+}
+
+/// Dragging while [`Action::PanCamera`] is held moves the view along with the cursor
+pub fn pan_camera(
+    actions: ActionInput,
+    size: Res<GridSize>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&mut Transform, &Projection), With<Camera2d>>,
+    mut previous_cursor: Local<Option<Vec2>>,
+) {
+    if !actions.pressed(Action::PanCamera) {
+        *previous_cursor = None;
+        return
+    }
+    let Some(cursor) = cursor_from_center(&window) else { return };
+
+    let (mut transform, projection) = camera.into_inner();
+    if let (Some(previous), Projection::Orthographic(ortho)) = (*previous_cursor, projection) {
+        let moved = (cursor - previous) * ortho.scale;
+        transform.translation.x -= moved.x;
+        transform.translation.y -= moved.y;
+        clamp_to_grid(&mut transform, *size);
+    }
+    *previous_cursor = Some(cursor);
+}
+
+/// [`Action::FitCamera`] centres the camera on the grid and zooms so all of it fits in the window
+pub fn fit_camera_on_key(
+    actions: ActionInput,
+    size: Res<GridSize>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&mut Transform, &mut Projection), With<Camera2d>>,
+) {
+    if actions.just_pressed(Action::FitCamera) {
+        fit_camera_to_grid(size, window, camera);
+    }
+}
+
+/// Refits the camera whenever the window changes size, including fullscreen switches
+pub fn fit_camera_on_resize(
+    mut resized: EventReader<WindowResized>,
+    size: Res<GridSize>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&mut Transform, &mut Projection), With<Camera2d>>,
+) {
+    if resized.read().last().is_some() {
+        fit_camera_to_grid(size, window, camera);
+    }
+}
+
+pub fn fit_camera_to_grid(
+    size: Res<GridSize>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&mut Transform, &mut Projection), With<Camera2d>>,
+) {
+    let (mut transform, mut projection) = camera.into_inner();
+    if let Projection::Orthographic(ortho) = projection.as_mut() {
+        ortho.scale = fit_scale(&window, *size);
+    }
+    transform.translation.x = 0.;
+    transform.translation.y = 0.;
+}
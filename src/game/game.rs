@@ -1,5 +1,5 @@
-use bevy::{app::{FixedUpdate, Plugin, Startup, Update}, core_pipeline::core_2d::Camera2d, ecs::{entity::Entity, query::With, schedule::{IntoScheduleConfigs, SystemSet}, system::{Commands, Res, ResMut, Single}}, input::{keyboard::KeyCode, ButtonInput}, log::info, render::camera::{OrthographicProjection, Projection}, state::{condition::in_state, state::{NextState, OnEnter, OnExit}}, ui::UiScale};
-use crate::{game::sandworld::{ image_setup::empty_grid_image_setup, main_interaction::main_interaction_loop, draw_image::draw_image, user_element_interraction::{user_adds_element, user_selects_element, UserSelectedElements}, ElemKind, GridParams}, utils::helper_utils::toggle_resolution, AppState};
+use bevy::{app::{FixedUpdate, Last, Plugin, Startup, Update}, core_pipeline::core_2d::Camera2d, ecs::{entity::Entity, query::With, resource::Resource, schedule::{common_conditions::resource_exists, Condition, IntoScheduleConfigs, SystemSet}, system::{Commands, Query, Res, ResMut, Single}}, log::info, render::{camera::{OrthographicProjection, Projection}, view::Visibility}, state::{app::AppExtStates, condition::in_state, state::{OnEnter, OnExit}}, ui::UiScale, window::{PrimaryWindow, Window}};
+use crate::{game::{background::{parallax_background, spawn_background, update_background}, gamepad_cursor::{draw_virtual_cursor, move_virtual_cursor, reset_virtual_cursor, spawn_virtual_cursor, VirtualCursor}, stats::{record_stat_events, save_stats_on_exit, save_stats_on_leave, track_play_time, StatEvent, Stats}, pause_menu::{pause_menu_action, setup_confirm_quit, setup_pause_menu, setup_pause_settings, user_toggles_pause, ConfirmQuitScreen, PauseMenuScreen, PauseMenuState, PauseSettingsScreen}, camera::{fit_camera_on_key, fit_camera_on_resize, fit_camera_to_grid, fit_scale, pan_camera, zoom_camera}, sandworld::{ debug_overlay::{draw_debug_overlay, spawn_debug_overlay, toggle_debug_overlays, DebugOverlays}, edit_history::{clear_edit_history, user_undo_redo, EditHistory}, image_setup::empty_grid_image_setup, main_interaction::main_interaction_loop, png_export::screenshot_on_key, recording::{capture_recording_frame, toggle_recording, Recorder}, simulation_control::{reset_simulation_control, user_controls_simulation, SimulationControl}, draw_image::draw_image, replay::{feed_replay_edits, live_input, reset_replay, start_replay_on_event, user_controls_replay, PlayReplay, ReplayState}, user_element_interraction::{apply_user_edits, user_adds_element, user_selects_element, UserEdit, UserSelectedElements}, ElemKind, GridParams, GridSize}, world_menu::{import_dropped_files, load_world_on_event, save_world_on_event, setup_world_menu, world_file_shortcuts, world_menu_action, LoadWorld, SaveWorld, WorldMenu}}, menu::menu::{despawn_screen, not_rebinding, stop_rebinding}, settings::apply_grid_size, utils::helper_utils::{fit_ui_to_window, toggle_fullscreen, ui_scale_for}, AppState, GameState};
 
 pub struct GamePlugin;
 impl Plugin for GamePlugin {
@@ -7,13 +7,73 @@ impl Plugin for GamePlugin {
 
         app
         .insert_resource(UserSelectedElements::single(ElemKind::Empty))
-        .add_systems(Startup, spawn_camera)
-        .add_systems(Update, toggle_resolution)
+        .init_resource::<EditHistory>()
+        .init_resource::<SimulationControl>()
+        .init_resource::<GridSize>()
+        .init_resource::<Recorder>()
+        .init_resource::<ReplayState>()
+        .init_resource::<VirtualCursor>()
+        .init_resource::<DebugOverlays>()
+        .add_event::<SaveWorld>()
+        .add_event::<LoadWorld>()
+        .add_event::<PlayReplay>()
+        .add_event::<UserEdit>()
+        .add_event::<StatEvent>()
+        .insert_resource(Stats::load_or_default())
+        .add_sub_state::<GameState>()
+        .add_sub_state::<PauseMenuState>()
+        .add_systems(Startup, (spawn_camera, spawn_virtual_cursor, spawn_background))
+        .add_systems(Update, (toggle_fullscreen.run_if(not_rebinding), fit_ui_to_window, fit_camera_on_resize))
+        .add_systems(Update, user_toggles_pause.run_if(in_state(AppState::InGame).and(not_rebinding)))
+        .add_systems(Update, (track_play_time.run_if(in_state(GameState::Running)), record_stat_events))
+        .add_systems(Last, save_stats_on_exit)
+        .add_systems(Update, 
+            (
+                user_undo_redo.run_if(live_input),
+                user_controls_simulation,
+                screenshot_on_key,
+                toggle_recording,
+                (zoom_camera, pan_camera, fit_camera_on_key),
+                move_virtual_cursor,
+                toggle_debug_overlays,
+            )
+                .run_if(in_state(GameState::Running))
+        )
+        .add_systems(Update, (draw_virtual_cursor, update_background, parallax_background))
+        .add_systems(OnExit(GameState::Running), reset_virtual_cursor)
+        .add_systems(Update, 
+            (
+                (
+                    (world_menu_action, world_file_shortcuts, import_dropped_files, user_controls_replay)
+                        .run_if(in_state(GameState::Running)),
+                    pause_menu_action,
+                ),
+                (save_world_on_event, load_world_on_event, start_replay_on_event),
+            )
+                .chain()
+                .run_if(in_state(AppState::InGame))
+        )
 
 
         .add_systems(OnEnter(AppState::InGame),
-            empty_grid_image_setup
+            (
+                (
+                    (despawn_world, clear_edit_history, reset_simulation_control, reset_replay, (apply_grid_size, fit_camera_to_grid).chain())
+                        .run_if(resource_exists::<NewWorld>),
+                    empty_grid_image_setup,
+                    spawn_debug_overlay,
+                )
+                    .chain(),
+                setup_world_menu,
+                show_grid,
+            )
         )
+        .add_systems(OnEnter(PauseMenuState::Main), setup_pause_menu)
+        .add_systems(OnExit(PauseMenuState::Main), despawn_screen::<PauseMenuScreen>)
+        .add_systems(OnEnter(PauseMenuState::Settings), setup_pause_settings)
+        .add_systems(OnExit(PauseMenuState::Settings), (despawn_screen::<PauseSettingsScreen>, stop_rebinding))
+        .add_systems(OnEnter(PauseMenuState::ConfirmQuit), setup_confirm_quit)
+        .add_systems(OnExit(PauseMenuState::ConfirmQuit), despawn_screen::<ConfirmQuitScreen>)
             
 
         .configure_sets(FixedUpdate, 
@@ -25,26 +85,45 @@ impl Plugin for GamePlugin {
                 .run_if(in_state(AppState::InGame))
                 .chain(),
         )
+        // The image is still redrawn while paused, so a world loaded from the pause menu shows up
+        .configure_sets(FixedUpdate, 
+            (
+                ElementSystem::MainInteractionLoop,
+                ElementSystem::UserElementGeneration,
+            )
+                .run_if(in_state(GameState::Running)),
+        )
         .add_systems(FixedUpdate, 
             (
                 main_interaction_loop.in_set(ElementSystem::MainInteractionLoop),
-                draw_image.in_set(ElementSystem::DrawOnImage),
+                (draw_image, capture_recording_frame, draw_debug_overlay)
+                    .chain()
+                    .in_set(ElementSystem::DrawOnImage),
                 (
-                    user_selects_element, 
-                    user_adds_element
+                    (
+                        user_selects_element, 
+                        user_adds_element
+                    )
+                        .run_if(live_input),
+                    feed_replay_edits,
+                    apply_user_edits,
                 )
+                    .chain()
                     .in_set(ElementSystem::UserElementGeneration),
-                back_to_main_menu.run_if(in_state(AppState::InGame))
             )
         )
 
         
         .add_systems(OnExit(AppState::InGame),
-            despawn_grid
+            (hide_grid, despawn_screen::<WorldMenu>, forget_new_world, save_stats_on_leave)
         );
     }
 }
 
+/// Inserted when New Game is chosen, the world of an earlier game is then discarded instead of continued
+#[derive(Resource)]
+pub struct NewWorld;
+
 #[derive(SystemSet, Hash, Debug, PartialEq, Eq, Clone)]
 pub enum ElementSystem {
     UserElementGeneration,
@@ -54,6 +133,8 @@ pub enum ElementSystem {
 
 fn spawn_camera(
     mut commands: Commands,
+    size: Res<GridSize>,
+    window: Single<&Window, With<PrimaryWindow>>,
     mut uiscale: ResMut<UiScale>
 ) {
     info!("Running the spawn_camera system");
@@ -61,20 +142,33 @@ fn spawn_camera(
         Camera2d,
         Projection::Orthographic(
             OrthographicProjection {
-                scale: 2.0,
+                scale: fit_scale(&window, *size),
                 ..OrthographicProjection::default_2d()
             }
         )
     ));
-    uiscale.0 = uiscale.0 / 2.;
+    uiscale.0 = ui_scale_for(&window);
 }
 
-fn back_to_main_menu(keys: Res<ButtonInput<KeyCode>>, mut app_s: ResMut<NextState<AppState>>) {
-    if keys.just_pressed(KeyCode::Escape) {
-        app_s.set(AppState::MainMenu)
+fn despawn_world(mut commands: Commands, grid: Query<Entity, With<GridParams>>) {
+    for entity in &grid {
+        commands.entity(entity).despawn();
     }
 }
 
-fn despawn_grid( mut commands: Commands, grid_e: Single<Entity, With<GridParams>> ) {
-    commands.entity(grid_e.into_inner()).despawn();
-}
\ No newline at end of file
+fn forget_new_world(mut commands: Commands) {
+    commands.remove_resource::<NewWorld>();
+}
+
+/// The grid is kept hidden behind the main menu so the game can be continued
+fn hide_grid(mut grid: Query<&mut Visibility, With<GridParams>>) {
+    for mut visibility in &mut grid {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn show_grid(mut grid: Query<&mut Visibility, With<GridParams>>) {
+    for mut visibility in &mut grid {
+        *visibility = Visibility::Inherited;
+    }
+}
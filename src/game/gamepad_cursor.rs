@@ -0,0 +1,84 @@
+use bevy::{color::Color, ecs::{change_detection::DetectChanges, component::Component, query::With, resource::Resource, system::{Commands, Query, Res, ResMut, Single}}, input::{gamepad::Gamepad, mouse::AccumulatedMouseMotion}, math::Vec2, render::view::Visibility, time::Time, ui::{BackgroundColor, BorderRadius, GlobalZIndex, Node, Outline, PositionType, UiScale, Val}, utils::default, window::{PrimaryWindow, Window}};
+
+/// Logical pixels per second the cursor moves with the left stick fully tilted
+const CURSOR_SPEED: f32 = 600.;
+/// Diameter of the drawn cursor, in UI pixels
+const CURSOR_SIZE: f32 = 12.;
+
+/// Painting cursor moved with the left stick of a gamepad, in logical window coordinates like [`Window::cursor_position`]
+///
+/// It appears in the middle of the window when the stick is first tilted and goes away as soon as the mouse moves
+#[derive(Resource, Default)]
+pub struct VirtualCursor {
+    pub position: Option<Vec2>,
+}
+impl VirtualCursor {
+    /// The virtual cursor while it is in use, otherwise the mouse cursor
+    pub fn position_in(&self, window: &Window) -> Option<Vec2> {
+        self.position.or_else(|| window.cursor_position())
+    }
+}
+
+#[derive(Component)]
+pub struct VirtualCursorMarker;
+
+pub fn spawn_virtual_cursor(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(CURSOR_SIZE),
+            height: Val::Px(CURSOR_SIZE),
+            ..default()
+        },
+        BackgroundColor(Color::WHITE),
+        Outline::new(Val::Px(2.), Val::ZERO, Color::BLACK),
+        BorderRadius::MAX,
+        GlobalZIndex(i32::MAX),
+        Visibility::Hidden,
+        VirtualCursorMarker,
+    ));
+}
+
+pub fn move_virtual_cursor(
+    gamepads: Query<&Gamepad>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    time: Res<Time>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut cursor: ResMut<VirtualCursor>,
+) {
+    if mouse_motion.delta != Vec2::ZERO && cursor.position.is_some() {
+        cursor.position = None;
+    }
+
+    let Some(stick) = gamepads.iter().map(Gamepad::left_stick).find(|stick| *stick != Vec2::ZERO) else { return };
+
+    let size = window.size();
+    let start = cursor.position.unwrap_or(size / 2.);
+    // The stick points up, window coordinates grow downwards
+    let moved = start + Vec2::new(stick.x, -stick.y) * CURSOR_SPEED * time.delta_secs();
+    cursor.position = Some(moved.clamp(Vec2::ZERO, size));
+}
+
+/// The cursor is put away while the game is paused or left, it reappears in the middle on the next tilt
+pub fn reset_virtual_cursor(mut cursor: ResMut<VirtualCursor>) {
+    cursor.position = None;
+}
+
+pub fn draw_virtual_cursor(
+    cursor: Res<VirtualCursor>,
+    ui_scale: Res<UiScale>,
+    marker: Single<(&mut Node, &mut Visibility), With<VirtualCursorMarker>>,
+) {
+    if !cursor.is_changed() { return }
+
+    let (mut node, mut visibility) = marker.into_inner();
+    match cursor.position {
+        Some(position) => {
+            let top_left = position / ui_scale.0 - CURSOR_SIZE / 2.;
+            node.left = Val::Px(top_left.x);
+            node.top = Val::Px(top_left.y);
+            *visibility = Visibility::Inherited;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
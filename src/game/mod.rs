@@ -1,3 +1,9 @@
+pub mod background;
+pub mod camera;
+#[allow(clippy::module_inception)]
 pub mod game;
+pub mod gamepad_cursor;
+pub mod pause_menu;
 pub mod sandworld;
-
+pub mod stats;
+pub mod world_menu;
@@ -0,0 +1,180 @@
+use bevy::{color::{palettes::css::YELLOW, Color}, ecs::{bundle::Bundle, component::Component, event::EventWriter, query::{Changed, With}, system::{Commands, Query, Res, ResMut}}, prelude::{children, SpawnRelated}, state::state::{NextState, State, StateSet, SubStates}, text::{TextColor, TextFont}, ui::{widget::{Button, Text}, AlignItems, FocusPolicy, BackgroundColor, FlexDirection, Interaction, JustifyContent, Node, UiRect, Val}, utils::default};
+use crate::{actions::{Action, ActionInput, ActionMap}, game::world_menu::{quicksave_path, LoadWorld, SaveWorld}, menu::menu::{spawn_settings_screen, NORMAL_BUTTON, TEXT_COLOR}, settings::Settings, AppState, GameState};
+
+/// Dims the world behind the pause menu screens
+const OVERLAY_COLOR: Color = Color::srgba(0., 0., 0., 0.5);
+
+/// Screen shown while the game is paused
+#[derive(SubStates, Default, Clone, Debug, Hash, Eq, PartialEq)]
+#[source(GameState = GameState::Paused)]
+pub enum PauseMenuState {
+    #[default]
+    Main,
+    Settings,
+    ConfirmQuit,
+}
+
+#[derive(Component)]
+pub struct PauseMenuScreen;
+
+#[derive(Component)]
+pub struct PauseSettingsScreen;
+
+#[derive(Component)]
+pub struct ConfirmQuitScreen;
+
+#[derive(Component)]
+pub enum PauseMenuAction {
+    Resume,
+    Save,
+    Load,
+    Settings,
+    QuitToMenu,
+    ConfirmQuit,
+    BackToPauseMenu,
+}
+
+/// [`Action::Pause`] pauses the game, and inside the pause menu goes back one screen or resumes
+pub fn user_toggles_pause(
+    actions: ActionInput,
+    game_state: Res<State<GameState>>,
+    pause_menu_state: Option<Res<State<PauseMenuState>>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut next_pause_menu_state: ResMut<NextState<PauseMenuState>>,
+) {
+    if !actions.just_pressed(Action::Pause) { return }
+
+    match (game_state.get(), pause_menu_state.as_deref().map(State::get)) {
+        (GameState::Running, _) => next_game_state.set(GameState::Paused),
+        (GameState::Paused, Some(PauseMenuState::Main) | None) => next_game_state.set(GameState::Running),
+        (GameState::Paused, Some(_)) => next_pause_menu_state.set(PauseMenuState::Main),
+    }
+}
+
+fn pause_button(label: &str, action: PauseMenuAction) -> impl Bundle {
+    (
+        Button,
+        Node {
+            width: Val::Px(300.0),
+            height: Val::Px(48.75),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(NORMAL_BUTTON),
+        action,
+        children![
+            (
+                Text::new(label),
+                TextFont {
+                    font_size: 33.0,
+                    ..default()
+                },
+                TextColor(TEXT_COLOR),
+            ),
+        ]
+    )
+}
+
+/// Full window overlay holding a centred panel, so the paused world stays visible behind it
+fn overlay(screen: impl Component, panel: impl Bundle) -> impl Bundle {
+    (
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        BackgroundColor(OVERLAY_COLOR),
+        FocusPolicy::Block,
+        screen,
+        children![(
+            Node {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(YELLOW.into()),
+            panel,
+        )],
+    )
+}
+
+fn title(text: &str) -> impl Bundle {
+    (
+        Text::new(text),
+        TextFont {
+            font_size: 50.0,
+            ..default()
+        },
+        TextColor(TEXT_COLOR),
+        Node {
+            margin: UiRect::all(Val::Px(30.0)),
+            ..default()
+        },
+    )
+}
+
+pub fn setup_pause_menu(mut commands: Commands) {
+    commands.spawn(overlay(
+        PauseMenuScreen,
+        children![
+            title("Paused"),
+            pause_button("Resume", PauseMenuAction::Resume),
+            pause_button("Save", PauseMenuAction::Save),
+            pause_button("Load", PauseMenuAction::Load),
+            pause_button("Settings", PauseMenuAction::Settings),
+            pause_button("Quit to Menu", PauseMenuAction::QuitToMenu),
+        ],
+    ));
+}
+
+pub fn setup_pause_settings(mut commands: Commands, settings: Res<Settings>, action_map: Res<ActionMap>) {
+    spawn_settings_screen(
+        &mut commands,
+        &settings,
+        &action_map,
+        (PauseSettingsScreen, BackgroundColor(OVERLAY_COLOR), FocusPolicy::Block),
+        PauseMenuAction::BackToPauseMenu,
+    );
+}
+
+/// The world is kept when quitting and can be continued from the main menu, until a new game is started
+pub fn setup_confirm_quit(mut commands: Commands) {
+    commands.spawn(overlay(
+        ConfirmQuitScreen,
+        children![
+            title("Quit to the main menu?"),
+            pause_button("Quit", PauseMenuAction::ConfirmQuit),
+            pause_button("Cancel", PauseMenuAction::BackToPauseMenu),
+        ],
+    ));
+}
+
+pub fn pause_menu_action(
+    interaction_query: Query<
+        (&Interaction, &PauseMenuAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut pause_menu_state: ResMut<NextState<PauseMenuState>>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut save_events: EventWriter<SaveWorld>,
+    mut load_events: EventWriter<LoadWorld>,
+) {
+    for (interaction, action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match action {
+                PauseMenuAction::Resume => game_state.set(GameState::Running),
+                PauseMenuAction::Save => { save_events.write(SaveWorld(quicksave_path())); },
+                PauseMenuAction::Load => { load_events.write(LoadWorld(quicksave_path())); },
+                PauseMenuAction::Settings => pause_menu_state.set(PauseMenuState::Settings),
+                PauseMenuAction::QuitToMenu => pause_menu_state.set(PauseMenuState::ConfirmQuit),
+                PauseMenuAction::ConfirmQuit => app_state.set(AppState::MainMenu),
+                PauseMenuAction::BackToPauseMenu => pause_menu_state.set(PauseMenuState::Main),
+            }
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use bevy::{asset::{Assets, Handle, RenderAssetUsages}, color::{Color, ColorToPacked, Mix}, core_pipeline::core_2d::Camera2d, ecs::{component::Component, entity::Entity, query::With, resource::Resource, system::{Commands, Local, Query, Res, ResMut, Single}}, image::Image, input::{keyboard::KeyCode, ButtonInput}, log::info, math::Vec3, render::{camera::Projection, render_resource::{Extent3d, TextureDimension, TextureFormat}, view::Visibility}, sprite::Sprite, transform::components::Transform, window::{PrimaryWindow, Window}};
+use crate::game::sandworld::{main_interaction::scans_left_to_right, simulation_control::SimulationControl, ElemKind, ElemPos, GridCells, GridParams, GridSize, GRID_SCALE};
+
+/// Overlay pixels per cell along each side, so grid lines fit between the cells
+const OVERLAY_SCALE: u32 = 4;
+/// Side of the square chunks the dirty chunk overlay splits the grid into, in cells
+pub const CHUNK_SIZE: u32 = 16;
+/// Width of the scan direction bars on both sides of the grid, in cells
+const SCAN_BAR_WIDTH: u32 = 2;
+/// Grid lines only show once a cell covers this many physical pixels
+const GRID_LINE_MIN_PIXELS: f32 = 12.;
+/// Share of its activity a cell keeps from one drawn frame to the next
+pub const ACTIVITY_DECAY: f32 = 0.9;
+/// Right above the grid sprite, in the scaled space of the grid
+const OVERLAY_Z: f32 = 0.1;
+
+const CHANGED_COLOR: Color = Color::srgba(1.0, 0.0, 1.0, 0.6);
+const CHUNK_COLOR: Color = Color::srgba(1.0, 0.9, 0.0, 0.8);
+const LEFT_TO_RIGHT_COLOR: Color = Color::srgba(0.1, 0.9, 0.3, 0.8);
+const RIGHT_TO_LEFT_COLOR: Color = Color::srgba(0.2, 0.4, 1.0, 0.8);
+const COLD_COLOR: Color = Color::srgba(0.0, 0.2, 1.0, 0.0);
+const HOT_COLOR: Color = Color::srgba(1.0, 0.1, 0.0, 0.7);
+const GRID_LINE_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.35);
+
+/// Keys toggling the overlays, in the order of the fields of [`DebugOverlays`]. They are fixed and cannot be
+/// bound to an [`Action`](crate::actions::Action)
+pub const OVERLAY_KEYS: [KeyCode; 5] = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5];
+
+/// Views into the automaton, each switched on and off with its function key while the game runs
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub struct DebugOverlays {
+    /// F1: cells whose element changed since the previous drawn frame
+    pub changed_cells: bool,
+    /// F2: [`CHUNK_SIZE`] chunks holding a changed cell, the ones a chunked simulation would have to update
+    pub dirty_chunks: bool,
+    /// F3: the direction every row was scanned in by the last step, see [`scans_left_to_right`]
+    pub scan_direction: bool,
+    /// F4: how often cells changed lately, a stand-in for velocity until particles carry one
+    pub activity_heatmap: bool,
+    /// F5: lines between the cells, once zoomed in far enough to see them
+    pub grid_lines: bool,
+}
+impl DebugOverlays {
+    pub fn any(&self) -> bool {
+        self.changed_cells || self.dirty_chunks || self.scan_direction || self.activity_heatmap || self.grid_lines
+    }
+}
+
+/// The image the overlays are drawn into, shown by a child sprite of the grid
+#[derive(Resource)]
+pub struct DebugOverlayImage(pub Handle<Image>);
+
+#[derive(Component)]
+pub struct DebugOverlaySprite;
+
+/// What the overlays remember between frames, dropped while they are all off
+#[derive(Default)]
+pub struct OverlayHistory {
+    previous: Option<Vec<ElemKind>>,
+    activity: Vec<f32>,
+}
+
+pub fn toggle_debug_overlays(keys: Res<ButtonInput<KeyCode>>, mut overlays: ResMut<DebugOverlays>) {
+    if !keys.any_just_pressed(OVERLAY_KEYS) { return }
+
+    let DebugOverlays { changed_cells, dirty_chunks, scan_direction, activity_heatmap, grid_lines } = overlays.as_mut();
+    let toggles = [
+        ("Changed cells", changed_cells),
+        ("Dirty chunks", dirty_chunks),
+        ("Scan direction", scan_direction),
+        ("Activity heatmap", activity_heatmap),
+        ("Grid lines", grid_lines),
+    ];
+    for (key, (name, enabled)) in OVERLAY_KEYS.into_iter().zip(toggles) {
+        if keys.just_pressed(key) {
+            *enabled = !*enabled;
+            info!("{name} overlay {}", if *enabled { "on" } else { "off" });
+        }
+    }
+}
+
+/// Gives the grid an overlay sprite, unless a continued world already has one
+pub fn spawn_debug_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    grid: Single<(Entity, &GridCells), With<GridParams>>,
+    existing: Query<(), With<DebugOverlaySprite>>,
+) {
+    if !existing.is_empty() { return }
+
+    let (grid, grid_cells) = *grid;
+    let handle = images.add(Image::new_fill(
+        Extent3d {
+            width: grid_cells.size().width * OVERLAY_SCALE,
+            height: grid_cells.size().height * OVERLAY_SCALE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    ));
+    commands.entity(grid).with_child((
+        Sprite::from_image(handle.clone()),
+        Transform::from_xyz(0., 0., OVERLAY_Z).with_scale(Vec3::splat(1. / OVERLAY_SCALE as f32)),
+        Visibility::Hidden,
+        DebugOverlaySprite,
+    ));
+    commands.insert_resource(DebugOverlayImage(handle));
+}
+
+/// Redraws the enabled overlays over the grid, after the grid image of the same frame
+pub fn draw_debug_overlay(
+    overlays: Res<DebugOverlays>,
+    grid_cells: Single<&GridCells>,
+    control: Res<SimulationControl>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    projection: Single<&Projection, With<Camera2d>>,
+    handle: Res<DebugOverlayImage>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprite: Single<&mut Visibility, With<DebugOverlaySprite>>,
+    mut history: Local<OverlayHistory>,
+) {
+    if !overlays.any() {
+        **sprite = Visibility::Hidden;
+        *history = OverlayHistory::default();
+        return
+    }
+    **sprite = Visibility::Inherited;
+
+    let kinds: Vec<ElemKind> = grid_cells.cells.iter().map(|elem| elem.kind()).collect();
+    let changed = match &history.previous {
+        Some(previous) => changed_cells(previous, &kinds),
+        None => vec![false; kinds.len()],
+    };
+    history.activity.resize(kinds.len(), 0.);
+    decay_activity(&mut history.activity, &changed);
+    history.previous = Some(kinds);
+
+    let pixels_per_cell = match *projection {
+        Projection::Orthographic(ortho) => GRID_SCALE * window.scale_factor() / ortho.scale,
+        _ => 0.,
+    };
+
+    let Some(data) = images.get_mut(&handle.0).and_then(|image| image.data.as_mut()) else { return };
+    data.fill(0);
+    let size = grid_cells.size();
+    let mut canvas = Canvas { data, width: size.width * OVERLAY_SCALE };
+
+    for pos in size.positions() {
+        let index = size.index(pos);
+        if overlays.activity_heatmap && history.activity[index] > 0.01 {
+            canvas.fill_cell(pos, COLD_COLOR.mix(&HOT_COLOR, history.activity[index].min(1.)));
+        }
+        if overlays.changed_cells && changed[index] {
+            canvas.fill_cell(pos, CHANGED_COLOR);
+        }
+    }
+
+    if overlays.dirty_chunks {
+        draw_dirty_chunks(&mut canvas, &changed, size);
+    }
+
+    if overlays.scan_direction {
+        // The last step ran on the tick before the current one
+        let tick = control.tick.saturating_sub(1);
+        for y in 0..size.height {
+            let color = if scans_left_to_right(y, tick) { LEFT_TO_RIGHT_COLOR } else { RIGHT_TO_LEFT_COLOR };
+            for x in (0..SCAN_BAR_WIDTH).chain(size.width - SCAN_BAR_WIDTH..size.width) {
+                canvas.fill_cell(ElemPos::new(x, y), color);
+            }
+        }
+    }
+
+    if overlays.grid_lines && pixels_per_cell >= GRID_LINE_MIN_PIXELS {
+        let rgba = GRID_LINE_COLOR.to_srgba().to_u8_array();
+        for pos in size.positions() {
+            for i in 0..OVERLAY_SCALE {
+                canvas.set(pos.x * OVERLAY_SCALE + i, pos.y * OVERLAY_SCALE, rgba);
+                canvas.set(pos.x * OVERLAY_SCALE, pos.y * OVERLAY_SCALE + i, rgba);
+            }
+        }
+    }
+}
+
+/// Which cells hold another element than before, both row-major like [`GridCells::cells`]
+pub fn changed_cells(previous: &[ElemKind], current: &[ElemKind]) -> Vec<bool> {
+    previous.iter().zip(current).map(|(before, after)| before != after).collect()
+}
+
+/// Fades the activity of every cell by [`ACTIVITY_DECAY`] and adds the rest for the cells that changed,
+/// so a cell changing every frame tends to 1
+pub fn decay_activity(activity: &mut [f32], changed: &[bool]) {
+    for (activity, changed) in activity.iter_mut().zip(changed) {
+        *activity = *activity * ACTIVITY_DECAY + if *changed { 1. - ACTIVITY_DECAY } else { 0. };
+    }
+}
+
+/// Column and row of every [`CHUNK_SIZE`] chunk of a grid of `size` holding a changed cell, row by row
+pub fn dirty_chunks(changed: &[bool], size: GridSize) -> Vec<(u32, u32)> {
+    let mut chunks = Vec::new();
+    for chunk_y in 0..size.height.div_ceil(CHUNK_SIZE) {
+        for chunk_x in 0..size.width.div_ceil(CHUNK_SIZE) {
+            let dirty = (0..CHUNK_SIZE * CHUNK_SIZE)
+                .map(|i| ElemPos::new(chunk_x * CHUNK_SIZE + i % CHUNK_SIZE, chunk_y * CHUNK_SIZE + i / CHUNK_SIZE))
+                .filter(|pos| pos.in_bounds(size))
+                .any(|pos| changed[size.index(pos)]);
+            if dirty {
+                chunks.push((chunk_x, chunk_y));
+            }
+        }
+    }
+    chunks
+}
+
+/// Outlines every chunk holding a changed cell
+fn draw_dirty_chunks(canvas: &mut Canvas, changed: &[bool], size: GridSize) {
+    let rgba = CHUNK_COLOR.to_srgba().to_u8_array();
+    let (chunk_pixels, width, height) = (CHUNK_SIZE * OVERLAY_SCALE, size.width * OVERLAY_SCALE, size.height * OVERLAY_SCALE);
+
+    for (chunk_x, chunk_y) in dirty_chunks(changed, size) {
+        let (left, top) = (chunk_x * chunk_pixels, chunk_y * chunk_pixels);
+        let (right, bottom) = ((left + chunk_pixels).min(width) - 1, (top + chunk_pixels).min(height) - 1);
+        for x in left..=right {
+            canvas.set(x, top, rgba);
+            canvas.set(x, bottom, rgba);
+        }
+        for y in top..=bottom {
+            canvas.set(left, y, rgba);
+            canvas.set(right, y, rgba);
+        }
+    }
+}
+
+/// Writes straight into the bytes of the overlay image, much faster than setting colours pixel by pixel
+struct Canvas<'a> {
+    data: &'a mut [u8],
+    /// In pixels
+    width: u32,
+}
+impl Canvas<'_> {
+    fn set(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        let start = ((y * self.width + x) * 4) as usize;
+        self.data[start..start + 4].copy_from_slice(&rgba);
+    }
+
+    fn fill_cell(&mut self, pos: ElemPos, color: Color) {
+        let rgba = color.to_srgba().to_u8_array();
+        for dy in 0..OVERLAY_SCALE {
+            for dx in 0..OVERLAY_SCALE {
+                self.set(pos.x * OVERLAY_SCALE + dx, pos.y * OVERLAY_SCALE + dy, rgba);
+            }
+        }
+    }
+}
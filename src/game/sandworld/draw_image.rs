@@ -1,23 +1,64 @@
-use bevy::{asset::Assets, ecs::system::{Res, ResMut, Single}, image::Image};
+use bevy::{asset::Assets, color::{Color, Luminance}, ecs::system::{Res, ResMut, Single}, image::Image};
 
-use crate::game::sandworld::{ElemPos, GridCells, GridImage, GRID_SIZE};
+use crate::{game::sandworld::{lighting::{apply_lighting, LightMap}, simulation_control::SimulationControl, wall_layer::WallCells, Elem, ElemKind, ElemPos, GridCells, GridImage}, settings::{ColorPalette, LightingMode, Settings}};
+
+/// How much darker the cells of a sand pattern are drawn
+const PATTERN_DARKENING: f32 = 0.15;
+
+/// How cells are coloured when the grid is drawn
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct GridStyle {
+    pub palette: ColorPalette,
+    /// Draws [`SandColor::has_pattern_at`](super::SandColor::has_pattern_at) over the sand
+    pub patterns: bool,
+    pub lighting: LightingMode,
+}
+impl GridStyle {
+    pub fn from_settings(settings: &Settings) -> Self {
+        GridStyle { palette: settings.palette, patterns: settings.sand_patterns, lighting: settings.lighting }
+    }
+
+    /// Colour of the image pixel at `pos`: the simulated cell, or the wall behind it where the cell is empty
+    pub fn pixel_color(&self, grid_cells: &GridCells, wall_cells: &WallCells, pos: ElemPos, tick: u64) -> Color {
+        let elem = grid_cells.get_elem_at(pos).unwrap();
+        match wall_cells.color_at(pos, self.palette) {
+            Some(wall_color) if elem.kind() == ElemKind::Empty => wall_color,
+            _ => self.cell_color(elem, pos, tick),
+        }
+    }
+
+    /// Colour of `elem` lying at `pos` on simulation tick `tick`
+    pub fn cell_color(&self, elem: Elem, pos: ElemPos, tick: u64) -> Color {
+        let color = elem.color(pos, tick, self.palette);
+        match elem.kind() {
+            ElemKind::Sand(sand_color) if self.patterns && sand_color.has_pattern_at(pos) => color.darker(PATTERN_DARKENING),
+            _ => color,
+        }
+    }
+}
 
 pub fn draw_image(
-    grid_cells: Single<&GridCells>,
+    grid: Single<(&GridCells, &WallCells)>,
     handle: Res<GridImage>,
+    settings: Res<Settings>,
+    control: Res<SimulationControl>,
     mut images: ResMut<Assets<Image>>,
 ) {
+    let (grid_cells, wall_cells) = grid.into_inner();
     let image = images.get_mut(&handle.0).expect("Image not found");
+    draw_grid_cells(grid_cells, wall_cells, image, GridStyle::from_settings(&settings), control.tick);
+}
 
-    for x in 0..GRID_SIZE.width {
-        for y in 0..GRID_SIZE.height {
-            let elem_pos = ElemPos::new(x, y);
-            let elem_color = grid_cells
-                .get_elem_at(elem_pos)
-                .unwrap()
-                .kind.get_varied_color_from_position(elem_pos);
-            
-            image.set_color_at(x, y, elem_color).unwrap();
-        }
+/// Redraws every cell of the grid over its wall into the image, then lights it unless the style turns lighting off
+pub fn draw_grid_cells(grid_cells: &GridCells, wall_cells: &WallCells, image: &mut Image, style: GridStyle, tick: u64) {
+    for elem_pos in grid_cells.size().positions() {
+        let elem_color = style.pixel_color(grid_cells, wall_cells, elem_pos, tick);
+
+        image.set_color_at(elem_pos.x, elem_pos.y, elem_color).unwrap();
+    }
+
+    if style.lighting != LightingMode::Off
+    && let Some(light_map) = LightMap::compute(grid_cells) {
+        apply_lighting(image, &light_map, style.lighting);
     }
-}
\ No newline at end of file
+}
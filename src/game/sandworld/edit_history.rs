@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+use bevy::{ecs::{event::EventWriter, resource::Resource, system::{Res, ResMut}}, input::{keyboard::KeyCode, ButtonInput}, log::info};
+use crate::game::sandworld::{user_element_interraction::UserEdit, Elem, ElemPos, GridCells};
+
+/// Upper bound for the memory held by the undo and redo stacks together
+pub const DEFAULT_HISTORY_BYTES: usize = 16 * 1024 * 1024;
+
+/// How a brush stroke is stored in the [`EditHistory`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HistoryMode {
+    /// Only the cells touched by the stroke are stored, undoing restores them and leaves the rest of the world running
+    Diff,
+    /// The whole grid is stored at the start of the stroke, undoing rolls the whole world back
+    Snapshot,
+}
+
+#[derive(Clone, Copy)]
+pub struct CellChange {
+    pub pos: ElemPos,
+    pub before: Elem,
+    pub after: Elem,
+}
+
+pub enum HistoryEntry {
+    Diff(Vec<CellChange>),
+    Snapshot(Box<GridCells>),
+}
+impl HistoryEntry {
+    fn size_bytes(&self) -> usize {
+        match self {
+            HistoryEntry::Diff(changes) => changes.capacity() * size_of::<CellChange>(),
+            HistoryEntry::Snapshot(grid_cells) => grid_cells.size_bytes(),
+        }
+    }
+
+    /// Applies the entry backwards, snapshots swap their stored grid with the current one.
+    /// Diffs only restore the cells that still hold what the stroke wrote, so whatever the running
+    /// simulation moved into the other cells since is kept
+    fn revert(&mut self, grid_cells: &mut GridCells) {
+        match self {
+            HistoryEntry::Diff(changes) => {
+                for change in changes.iter().rev() {
+                    if grid_cells.get_elem_at(change.pos).is_some_and(|elem| elem.kind == change.after.kind) {
+                        grid_cells.set_elem_at(change.pos, Elem { moved: false, ..change.before });
+                    }
+                }
+            }
+            HistoryEntry::Snapshot(grid) => std::mem::swap(grid.as_mut(), grid_cells),
+        }
+    }
+
+    /// Applies the entry forwards, snapshots swap their stored grid with the current one.
+    /// Diffs only write the cells that still hold what they held before the stroke
+    fn reapply(&mut self, grid_cells: &mut GridCells) {
+        match self {
+            HistoryEntry::Diff(changes) => {
+                for change in changes.iter() {
+                    if grid_cells.get_elem_at(change.pos).is_some_and(|elem| elem.kind == change.before.kind) {
+                        grid_cells.set_elem_at(change.pos, Elem { moved: false, ..change.after });
+                    }
+                }
+            }
+            HistoryEntry::Snapshot(grid) => std::mem::swap(grid.as_mut(), grid_cells),
+        }
+    }
+}
+
+/// Stores every user brush stroke (press to release) so it can be undone with Ctrl+Z and redone with Ctrl+Y
+///
+/// Memory is bounded by `max_bytes`, the oldest strokes are dropped first
+#[derive(Resource)]
+pub struct EditHistory {
+    pub mode: HistoryMode,
+    pub max_bytes: usize,
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    stroke: Option<HistoryEntry>,
+    stroke_changed: bool,
+    used_bytes: usize,
+}
+impl Default for EditHistory {
+    fn default() -> Self {
+        EditHistory::new(HistoryMode::Diff, DEFAULT_HISTORY_BYTES)
+    }
+}
+impl EditHistory {
+    pub fn new(mode: HistoryMode, max_bytes: usize) -> Self {
+        EditHistory {
+            mode,
+            max_bytes,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            stroke: None,
+            stroke_changed: false,
+            used_bytes: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.stroke = None;
+        self.stroke_changed = false;
+        self.used_bytes = 0;
+    }
+
+    /// Opens a new stroke if none is in progress
+    pub fn begin_stroke(&mut self, grid_cells: &GridCells) {
+        if self.stroke.is_none() {
+            self.stroke = Some(match self.mode {
+                HistoryMode::Diff => HistoryEntry::Diff(Vec::new()),
+                HistoryMode::Snapshot => HistoryEntry::Snapshot(Box::new(grid_cells.clone())),
+            });
+            self.stroke_changed = false;
+        }
+    }
+
+    /// Records a single cell write of the current stroke, writes that do not change the kind are ignored
+    pub fn record(&mut self, pos: ElemPos, before: Elem, after: Elem) {
+        if before.kind == after.kind { return }
+
+        match &mut self.stroke {
+            Some(HistoryEntry::Diff(changes)) => changes.push(CellChange { pos, before, after }),
+            Some(HistoryEntry::Snapshot(_)) => {},
+            None => return,
+        }
+        self.stroke_changed = true;
+    }
+
+    /// Closes the current stroke and pushes it onto the undo stack, clearing the redo stack
+    pub fn end_stroke(&mut self) {
+        if let Some(mut entry) = self.stroke.take() {
+            if !self.stroke_changed { return }
+            self.stroke_changed = false;
+
+            if let HistoryEntry::Diff(changes) = &mut entry {
+                changes.shrink_to_fit();
+            }
+            for dropped in self.redo.drain(..) {
+                self.used_bytes -= dropped.size_bytes();
+            }
+            self.push_undo(entry);
+        }
+    }
+
+    pub fn undo(&mut self, grid_cells: &mut GridCells) -> bool {
+        self.end_stroke();
+
+        if let Some(mut entry) = self.undo.pop_back() {
+            entry.revert(grid_cells);
+            self.redo.push(entry);
+            true
+        } else { false }
+    }
+
+    pub fn redo(&mut self, grid_cells: &mut GridCells) -> bool {
+        self.end_stroke();
+
+        if let Some(mut entry) = self.redo.pop() {
+            entry.reapply(grid_cells);
+            self.undo.push_back(entry);
+            true
+        } else { false }
+    }
+
+    /// Number of strokes that can be undone
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Memory held by the undo and redo stacks, kept under `max_bytes` unless a single stroke is larger
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    fn push_undo(&mut self, entry: HistoryEntry) {
+        self.used_bytes += entry.size_bytes();
+        self.undo.push_back(entry);
+
+        while self.used_bytes > self.max_bytes && self.undo.len() > 1 {
+            if let Some(dropped) = self.undo.pop_front() {
+                self.used_bytes -= dropped.size_bytes();
+            }
+        }
+    }
+}
+
+/// Ctrl+Z undoes the last stroke, Ctrl+Y or Ctrl+Shift+Z redoes it, Ctrl+H switches the [`HistoryMode`] for the next strokes
+///
+/// Runs in [`Update`](bevy::app::Update) so it works whether the simulation is running or not,
+/// the undo and redo themselves are sent as [`UserEdit`]s
+pub fn user_undo_redo(
+    keys: Res<ButtonInput<KeyCode>>,
+    history: Res<EditHistory>,
+    mut edits: EventWriter<UserEdit>,
+) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) { return }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if keys.just_pressed(KeyCode::KeyZ) && !shift {
+        edits.write(UserEdit::Undo);
+    } else if keys.just_pressed(KeyCode::KeyY)
+    || (keys.just_pressed(KeyCode::KeyZ) && shift) {
+        edits.write(UserEdit::Redo);
+    } else if keys.just_pressed(KeyCode::KeyH) {
+        let mode = match history.mode {
+            HistoryMode::Diff => HistoryMode::Snapshot,
+            HistoryMode::Snapshot => HistoryMode::Diff,
+        };
+        edits.write(UserEdit::SetHistoryMode(mode));
+        info!("Edit history mode set to {:?}", mode);
+    }
+}
+
+pub fn clear_edit_history(mut history: ResMut<EditHistory>) {
+    history.clear();
+}
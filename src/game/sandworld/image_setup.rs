@@ -1,30 +1,20 @@
-use bevy::{asset::{Assets, RenderAssetUsages}, color::ColorToPacked, ecs::system::{Commands, ResMut}, image::Image, math::Vec3, render::render_resource::{Extent3d, TextureDimension, TextureFormat}, sprite::Sprite, transform::components::Transform};
-use crate::game::sandworld::{GridCells, GridImage, GridParams, EMPTY_COLOR, GRID_SCALE, GRID_SIZE};
+use bevy::{asset::{Assets, RenderAssetUsages}, color::ColorToPacked, ecs::{query::With, system::{Commands, Query, Res, ResMut}}, image::Image, math::Vec3, render::render_resource::{Extent3d, TextureDimension, TextureFormat}, sprite::Sprite, transform::components::Transform};
+use crate::game::sandworld::{wall_layer::WallCells, GridCells, GridImage, GridParams, GridSize, EMPTY_COLOR, GRID_SCALE};
 
-/// Creates an black image of a certain size at the center of the world, upscaled by the scaling factor 
+/// Creates an black image of the [`GridSize`] at the center of the world, upscaled by the scaling factor 
+///
+/// Does nothing if a grid already exists, as when a game is continued
 pub fn empty_grid_image_setup(
     mut commands: Commands,
-    mut images: ResMut<Assets<Image>>
+    mut images: ResMut<Assets<Image>>,
+    size: Res<GridSize>,
+    existing: Query<(), With<GridParams>>,
 ) {
-    let grid = GridParams { scale: GRID_SCALE };
+    if !existing.is_empty() { return }
 
-    // Create an image that we are going to draw into
-    let image = Image::new_fill(
-        // 2D image of size 256x256
-        Extent3d {
-            width: GRID_SIZE.width,
-            height: GRID_SIZE.height,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        // Initialize it with a transparent black color
-        &(EMPTY_COLOR.to_srgba().to_u8_array()),
-        // Use the same encoding as the color we set
-        TextureFormat::Rgba8UnormSrgb,
-        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
-    );
+    let grid = GridParams { scale: GRID_SCALE };
 
-    let handle = images.add(image);
+    let handle = images.add(new_grid_image(*size));
     let transform = Transform::from_xyz(0., 0., 0.)
             .with_scale(Vec3::splat(grid.scale));
 
@@ -32,8 +22,28 @@ pub fn empty_grid_image_setup(
         Sprite::from_image(handle.clone()),
         transform,
         grid,
-        GridCells::new_empty(),
+        GridCells::new_sized(*size),
+        WallCells::new_sized(*size),
     ));
     
     commands.insert_resource(GridImage(handle));
 }
+
+/// A grid sized image filled with the empty color, in the format [`draw_grid_cells`](super::draw_image::draw_grid_cells) draws with
+pub fn new_grid_image(size: GridSize) -> Image {
+    // Create an image that we are going to draw into
+    Image::new_fill(
+        // 2D image of the grid size
+        Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        // Initialize it with a transparent black color
+        &(EMPTY_COLOR.to_srgba().to_u8_array()),
+        // Use the same encoding as the color we set
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    )
+}
@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+use bevy::{color::{Alpha, Color, ColorToComponents, LinearRgba}, image::Image};
+use crate::{game::sandworld::{ElemKind, ElemPos, GridCells, GridSize}, settings::LightingMode};
+
+/// Light level of the brightest emitters, light loses one level per cell it travels
+pub const MAX_LIGHT: u8 = 12;
+/// Share of the full brightness cells keep when no light reaches them
+const AMBIENT_LIGHT: f32 = 0.55;
+/// Colour of the halo drawn into empty cells by [`LightingMode::Glow`]
+const HALO_COLOR: Color = Color::srgb(1.0, 0.65, 0.25);
+/// Opacity of the halo next to an emitter of [`MAX_LIGHT`]
+const HALO_ALPHA: f32 = 0.6;
+
+/// Light level of every cell of the grid, row-major like [`GridCells::cells`]
+pub struct LightMap {
+    size: GridSize,
+    levels: Vec<u8>,
+}
+impl LightMap {
+    /// Lights the grid with the elements that give off light, see [`ElemKind::light_emission`].
+    /// `None` when nothing gives off light, the grid is then drawn unlit rather than dimmed all over
+    pub fn compute(grid_cells: &GridCells) -> Option<Self> {
+        let sources: Vec<(ElemPos, u8)> = grid_cells.size().positions()
+            .filter_map(|pos| {
+                let emission = grid_cells.get_elem_at(pos).unwrap().kind.light_emission();
+                (emission > 0).then_some((pos, emission))
+            })
+            .collect();
+        if sources.is_empty() { return None }
+
+        Some(LightMap::from_sources(grid_cells, sources))
+    }
+
+    /// Spreads the light of `sources` through the empty cells of the grid, losing a level per cell.
+    /// Solid cells are lit on the side facing the light but stop it
+    pub fn from_sources(grid_cells: &GridCells, sources: impl IntoIterator<Item = (ElemPos, u8)>) -> Self {
+        let size = grid_cells.size();
+        let mut levels = vec![0; grid_cells.cells.len()];
+        let mut queue = VecDeque::new();
+        for (pos, level) in sources {
+            if !pos.in_bounds(size) { continue }
+
+            let (index, level) = (size.index(pos), level.min(MAX_LIGHT));
+            if levels[index] < level {
+                levels[index] = level;
+                queue.push_back(pos);
+            }
+        }
+
+        while let Some(pos) = queue.pop_front() {
+            let spread = levels[size.index(pos)].saturating_sub(1);
+            if spread == 0 { continue }
+
+            for neighbour in neighbours(pos, size) {
+                let index = size.index(neighbour);
+                if levels[index] >= spread { continue }
+
+                levels[index] = spread;
+                if grid_cells.cells[index].kind == ElemKind::Empty {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        LightMap { size, levels }
+    }
+
+    /// Between 0 and 1
+    pub fn light_at(&self, pos: ElemPos) -> f32 {
+        self.levels[self.size.index(pos)] as f32 / MAX_LIGHT as f32
+    }
+
+    /// Darkens the pixel `color` down to the ambient light where no light reaches. With [`LightingMode::Glow`]
+    /// lit transparent pixels get a halo instead of staying transparent
+    pub fn lit_color(&self, color: Color, pos: ElemPos, mode: LightingMode) -> Color {
+        let light = self.light_at(pos);
+        let transparent = color.alpha() == 0.;
+        match mode {
+            LightingMode::Off => color,
+            LightingMode::Glow if transparent && light > 0. => HALO_COLOR.with_alpha(light * HALO_ALPHA),
+            _ if transparent => color,
+            _ => {
+                let brightness = AMBIENT_LIGHT + (1. - AMBIENT_LIGHT) * light;
+                let linear = color.to_linear();
+                LinearRgba::from_vec3(linear.to_vec3() * brightness).with_alpha(linear.alpha).into()
+            }
+        }
+    }
+}
+
+/// Multiplies the light map into an image drawn by [`draw_grid_cells`](super::draw_image::draw_grid_cells), as a pass
+/// of its own so it can be timed and switched off separately
+pub fn apply_lighting(image: &mut Image, light_map: &LightMap, mode: LightingMode) {
+    if mode == LightingMode::Off { return }
+
+    for pos in light_map.size.positions() {
+        let color = image.get_color_at(pos.x, pos.y).unwrap();
+        image.set_color_at(pos.x, pos.y, light_map.lit_color(color, pos, mode)).unwrap();
+    }
+}
+
+fn neighbours(pos: ElemPos, size: GridSize) -> impl Iterator<Item = ElemPos> {
+    [(0, -1), (-1, 0), (1, 0), (0, 1)]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let x = pos.x.checked_add_signed(dx)?;
+            let y = pos.y.checked_add_signed(dy)?;
+            Some(ElemPos::new(x, y)).filter(|pos| pos.in_bounds(size))
+        })
+}
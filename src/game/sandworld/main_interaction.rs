@@ -1,22 +1,32 @@
-use bevy::ecs::system::{Local, Single};
-use crate::game::sandworld::{Elem, ElemKind, ElemPos, GridCells, GRID_SIZE};
+use bevy::ecs::{event::EventWriter, system::{ResMut, Single}};
+use crate::game::{sandworld::{simulation_control::SimulationControl, Elem, ElemKind, ElemPos, GridCells}, stats::StatEvent};
 
+/// Runs as many simulation steps as the [`SimulationControl`] allows for this fixed tick
 pub fn main_interaction_loop(
     mut grid_cells: Single<&mut GridCells>,
-    mut dir: Local<bool>
+    mut control: ResMut<SimulationControl>,
+    mut stat_events: EventWriter<StatEvent>,
 ) {
+    let steps = control.take_steps();
+    for _ in 0..steps {
+        simulation_step(grid_cells.as_mut(), control.tick);
+        control.tick += 1;
+    }
+    if steps > 0 {
+        stat_events.write(StatEvent::TicksSimulated(steps as u64));
+    }
+}
 
-    let grid_cells = grid_cells.as_mut();
+/// Advances the whole grid by a single step, the scan direction of each row alternates with the tick
+pub fn simulation_step(grid_cells: &mut GridCells, tick: u64) {
+    let dir = tick % 2 == 1;
+    let size = grid_cells.size();
 
-    for y in (0..GRID_SIZE.height).rev() {
+    for y in (0..size.height).rev() {
 
-        let x_range = 
-            if y % 2 == 0 
-            && *dir { (0..GRID_SIZE.width).collect::<Vec<u32>>() }
-            else if y % 2 != 0
-            && *dir { (0..GRID_SIZE.width).rev().collect::<Vec<u32>>() }
-            else if y % 2 == 0 { (0..GRID_SIZE.width).rev().collect::<Vec<u32>>() }
-            else { (0..GRID_SIZE.width).collect::<Vec<u32>>() };
+        let x_range: Vec<u32> = 
+            if scans_left_to_right(y, tick) { (0..size.width).collect() }
+            else { (0..size.width).rev().collect() };
 
         for x in x_range {
             let pos = ElemPos::new(x, y);
@@ -26,70 +36,76 @@ pub fn main_interaction_loop(
                 match elem.kind {
                     ElemKind::Empty | ElemKind::Stone => continue,
                     ElemKind::Sand(_) => {
-                        sand_algorithm(grid_cells, pos, *dir, elem.kind);
+                        sand_algorithm(grid_cells, pos, dir, elem);
                     },
                 }
             } else {
-                grid_cells.set_elem_at(pos, Elem::new( elem.kind, false));
+                grid_cells.set_elem_at(pos, Elem { moved: false, ..elem });
             }
         }
     }
-    if *dir { *dir = false } else { *dir = true }
 }
 
+/// Whether row `y` is scanned from left to right on `tick`, the direction alternates between rows and ticks
+pub fn scans_left_to_right(y: u32, tick: u64) -> bool {
+    y.is_multiple_of(2) != tick.is_multiple_of(2)
+}
+
+/// Moves the sand grain down, or diagonally down in the order given by `dir`. Returns whether it moved
 fn sand_algorithm(
     grid_cells: &mut GridCells,
     pos: ElemPos,
     dir: bool,
-    sand: ElemKind
-) {
-    if pos.in_border_bottom() {
-        let permb_elems = vec![ElemKind::Empty];
+    sand: Elem
+) -> bool {
+    if !pos.in_border_bottom(grid_cells.size()) { return false }
 
-        if unchecked_set_color_down(grid_cells, pos, sand, &permb_elems) { return }
-        else if dir {
-            if set_color_leftdown(grid_cells, pos, sand, &permb_elems) { return }
-            else if set_color_rightdown(grid_cells, pos, sand, &permb_elems) { return }
-        } else {
-            if set_color_rightdown(grid_cells, pos, sand, &permb_elems) { return }
-            else if set_color_leftdown(grid_cells, pos, sand, &permb_elems) { return }
-        }
-    }
+    let permb_elems = [ElemKind::Empty];
+
+    let (first_diagonal, second_diagonal): (MoveFn, MoveFn) = 
+        if dir { (set_color_leftdown, set_color_rightdown) }
+        else { (set_color_rightdown, set_color_leftdown) };
+
+    unchecked_set_color_down(grid_cells, pos, sand, &permb_elems)
+    || first_diagonal(grid_cells, pos, sand, &permb_elems)
+    || second_diagonal(grid_cells, pos, sand, &permb_elems)
 }
 
-fn unchecked_set_color_down(grid_cells: &mut GridCells, pos: ElemPos, kind: ElemKind, permb_elems: &Vec<ElemKind>) -> bool {
+type MoveFn = fn(&mut GridCells, ElemPos, Elem, &[ElemKind]) -> bool;
+
+fn unchecked_set_color_down(grid_cells: &mut GridCells, pos: ElemPos, elem: Elem, permb_elems: &[ElemKind]) -> bool {
     let down_pos = ElemPos::new(pos.x, pos.y + 1);
-    let check_kind = grid_cells.get_elem_at(down_pos).unwrap().kind;
-    if permb_elems.contains(&check_kind) {
-        grid_cells.set_elem_at(pos, Elem::new(check_kind, false)).unwrap();
-        grid_cells.set_elem_at(down_pos, Elem::new(kind, false)).unwrap();
+    let check = grid_cells.get_elem_at(down_pos).unwrap();
+    if permb_elems.contains(&check.kind) {
+        grid_cells.set_elem_at(pos, Elem { moved: false, ..check }).unwrap();
+        grid_cells.set_elem_at(down_pos, Elem { moved: false, ..elem }).unwrap();
         return true
     }
-    return false
+    false
 }
 
-fn set_color_leftdown(grid_cells: &mut GridCells, pos: ElemPos, kind: ElemKind, permb_elems: &Vec<ElemKind>) -> bool {
+fn set_color_leftdown(grid_cells: &mut GridCells, pos: ElemPos, elem: Elem, permb_elems: &[ElemKind]) -> bool {
     if pos.in_border_left() {
         let leftdown_pos = ElemPos::new(pos.x - 1, pos.y + 1);
-        let check_kind = grid_cells.get_elem_at(leftdown_pos).unwrap().kind;
-        if permb_elems.contains(&check_kind) {
-            grid_cells.set_elem_at(pos, Elem::new(check_kind, false)).unwrap();
-            grid_cells.set_elem_at(leftdown_pos, Elem::new(kind, true)).unwrap();
+        let check = grid_cells.get_elem_at(leftdown_pos).unwrap();
+        if permb_elems.contains(&check.kind) {
+            grid_cells.set_elem_at(pos, Elem { moved: false, ..check }).unwrap();
+            grid_cells.set_elem_at(leftdown_pos, Elem { moved: true, ..elem }).unwrap();
             return true
         }
     }
-    return false
+    false
 }
 
-fn set_color_rightdown(grid_cells: &mut GridCells, pos: ElemPos, kind: ElemKind, permb_elems: &Vec<ElemKind>) -> bool {
-    if pos.in_border_right() {
+fn set_color_rightdown(grid_cells: &mut GridCells, pos: ElemPos, elem: Elem, permb_elems: &[ElemKind]) -> bool {
+    if pos.in_border_right(grid_cells.size()) {
         let rightdown_pos = ElemPos::new(pos.x + 1, pos.y + 1);
-        let check_kind = grid_cells.get_elem_at(rightdown_pos).unwrap().kind;
-        if permb_elems.contains(&check_kind) {
-            grid_cells.set_elem_at(pos, Elem::new(check_kind, false)).unwrap();
-            grid_cells.set_elem_at(rightdown_pos, Elem::new(kind, true)).unwrap();
+        let check = grid_cells.get_elem_at(rightdown_pos).unwrap();
+        if permb_elems.contains(&check.kind) {
+            grid_cells.set_elem_at(pos, Elem { moved: false, ..check }).unwrap();
+            grid_cells.set_elem_at(rightdown_pos, Elem { moved: true, ..elem }).unwrap();
             return true
         }
     }
-    return false
+    false
 }
\ No newline at end of file
@@ -1,143 +1,271 @@
 use std::{fmt::Display};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 
 use bevy::asset::Handle;
 use bevy::ecs::resource::Resource;
 use bevy::image::Image;
-use bevy::{color::Color, ecs::component::Component};
+use bevy::{color::{Color, Luminance}, ecs::component::Component};
 
+use crate::settings::ColorPalette;
+
+pub mod debug_overlay;
 pub mod draw_image;
+pub mod edit_history;
 pub mod image_setup;
+pub mod lighting;
 pub mod user_element_interraction;
 pub mod main_interaction;
+pub mod png_export;
+pub mod png_import;
+pub mod replay;
+pub mod recording;
+pub mod scenario;
+pub mod simulation_control;
+pub mod wall_layer;
+pub mod world_file;
 
-const GRID_SIZE: GridSize = GridSize::new(256, 192);
-const GRID_SCALE: f32 = 5.;
+/// Size of a new grid unless the settings pick another of [`GridSize::ALL`]
+pub const GRID_SIZE: GridSize = GridSize::new(256, 192);
+pub const GRID_SCALE: f32 = 5.;
 const EMPTY_COLOR: Color = Color::srgba(0., 0., 0., 0.);
+/// How much darker [`ColorVariation::DepthGradient`] elements are at the depth of the bottom of a [`GRID_SIZE`] grid than at the top
+const DEPTH_DARKENING: f32 = 0.25;
 
 #[derive(Resource)]
 pub struct GridImage(pub Handle<Image>);
 
+/// Width and height of the grid in cells, chosen when a new world starts and kept for its lifetime.
+/// As a resource it is the size of the current world, which the camera and the background fit to
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct GridSize{
-    width: u32,
-    height: u32,
+    pub width: u32,
+    pub height: u32,
 }
 impl GridSize {
-    const fn new(width: u32, height: u32) -> Self {
+    /// Sizes offered by the settings screen
+    pub const ALL: [GridSize; 3] = [GridSize::new(128, 96), GRID_SIZE, GridSize::new(384, 288)];
+
+    pub const fn new(width: u32, height: u32) -> Self {
         GridSize { width, height }
     }
-    const fn count(&self) -> usize {
+    pub const fn count(&self) -> usize {
         (self.width * self.height) as usize
     }
+    /// Index of `pos` in [`GridCells::cells`], row by row
+    pub fn index(&self, pos: ElemPos) -> usize {
+        (pos.y * self.width + pos.x) as usize
+    }
+    /// Every position of the grid, row by row
+    pub fn positions(self) -> impl Iterator<Item = ElemPos> {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| ElemPos::new(x, y)))
+    }
+}
+impl Default for GridSize {
+    fn default() -> Self {
+        GRID_SIZE
+    }
 }
 
 #[derive(Component)]
 pub struct GridParams {
     pub scale: f32,
 }
-#[derive(Component)]
+/// The simulated cells, drawn over the [`WallCells`](wall_layer::WallCells) of the same entity
+#[derive(Component, Clone)]
+#[require(wall_layer::WallCells)]
 pub struct GridCells {
-    pub cells: [Elem; GRID_SIZE.count()]
+    size: GridSize,
+    /// Row by row, see [`GridSize::index`]
+    pub cells: Vec<Elem>,
 }
 impl GridCells {
+    /// An empty grid of the default [`GRID_SIZE`]
     pub fn new_empty() -> Self {
-        GridCells { cells: [ Elem::new(ElemKind::Empty, false) ; GRID_SIZE.count() ] }
+        GridCells::new_sized(GRID_SIZE)
+    }
+    pub fn new_sized(size: GridSize) -> Self {
+        GridCells { size, cells: vec![ Elem::new(ElemKind::Empty, false) ; size.count() ] }
+    }
+    pub fn size(&self) -> GridSize {
+        self.size
+    }
+    /// Memory held by the grid, its cells included
+    pub fn size_bytes(&self) -> usize {
+        size_of::<GridCells>() + self.cells.capacity() * size_of::<Elem>()
     }
     pub fn get_elem_at(&self, pos: ElemPos) -> Option<Elem> {
-        if pos.in_bounds() {
-            Some( self.cells[(pos.y * GRID_SIZE.width + pos.x) as usize] )
+        if pos.in_bounds(self.size) {
+            Some( self.cells[self.size.index(pos)] )
         } else { None }
     }
     pub fn set_elem_at(&mut self, pos: ElemPos, elem: Elem) -> Option<()> {
-        if pos.in_bounds() {
-            self.cells[(pos.y * GRID_SIZE.width + pos.x) as usize] = elem; 
+        if pos.in_bounds(self.size) {
+            let index = self.size.index(pos);
+            self.cells[index] = elem; 
 
             Some(())
         } else { None }
     }
+    /// Number of cells of every kind, in the order of [`ElemKind::ALL`]
+    pub fn count_kinds(&self) -> [(ElemKind, usize); ElemKind::ALL.len()] {
+        let mut counts = ElemKind::ALL.map(|kind| (kind, 0));
+        for elem in self.cells.iter() {
+            if let Some((_, count)) = counts.iter_mut().find(|(kind, _)| *kind == elem.kind) {
+                *count += 1;
+            }
+        }
+        counts
+    }
 }
 
 #[derive(Copy, Clone)]
 pub struct Elem {
     kind: ElemKind,
-    moved: bool
+    moved: bool,
+    /// Picks the shade of the particle, it travels with the particle so its colour stays put while it falls
+    seed: u16,
 }
 impl Elem {
+    /// An element without a seed, see [`Elem::seeded_at`]
     pub fn new(kind: ElemKind, moved: bool) -> Self {
-        Elem { kind, moved }
+        Elem { kind, moved, seed: 0 }
+    }
+
+    /// Gives a particle created at `pos` its seed. Derived from the position so replays and reloads
+    /// shade the same particles the same way
+    pub fn seeded_at(self, pos: ElemPos) -> Self {
+        Elem { seed: hash_values(&[pos.x as u64, pos.y as u64]) as u16, ..self }
+    }
+
+    /// Gives a particle painted at `pos` on simulation tick `tick` its seed, so the grains a brush held still
+    /// keeps pouring from the same spot get different shades while replays still shade them the same way
+    pub fn seeded_at_tick(self, pos: ElemPos, tick: u64) -> Self {
+        Elem { seed: hash_values(&[pos.x as u64, pos.y as u64, tick]) as u16, ..self }
+    }
+
+    pub fn kind(&self) -> ElemKind {
+        self.kind
+    }
+
+    pub fn seed(&self) -> u16 {
+        self.seed
+    }
+
+    /// The colour of the particle at `pos` on simulation tick `tick`, following its [`ColorVariation`]
+    pub fn color(&self, pos: ElemPos, tick: u64, palette: ColorPalette) -> Color {
+        match self.kind.color_variation() {
+            ColorVariation::Flat => self.kind.get_base_color(palette),
+            ColorVariation::Noise => self.kind.get_varied_color(self.seed as u64, palette),
+            ColorVariation::DepthGradient => {
+                // Relative to the default height, so cells darken by the same step at every grid size
+                let depth = (pos.y as f32 / GRID_SIZE.height as f32).min(1.);
+                self.kind.get_base_color(palette).darker(DEPTH_DARKENING * depth)
+            }
+            ColorVariation::Flicker => self.kind.get_varied_color(hash_values(&[self.seed as u64, tick]), palette),
+        }
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+/// Folds the values through the SplitMix64 finalizer. Unlike the hashers of the standard library the result is
+/// fixed, so seeds and shades stay the same across Rust releases and in replays recorded by older builds
+fn hash_values(values: &[u64]) -> u64 {
+    values.iter().fold(0, |hash, value| {
+        let mut z = (hash ^ value).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    })
+}
+
+/// How the shade of an element strays from its base colour, see [`ElemKind::color_variation`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorVariation {
+    /// Always the base colour
+    Flat,
+    /// A fixed shade per particle, picked by its seed
+    Noise,
+    /// Darker the deeper the cell lies in the grid, for masses that read as one block
+    DepthGradient,
+    /// A new shade of the particle every tick, for burning elements
+    Flicker,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ElemKind {
     Empty,
     Stone,
     Sand(SandColor),
 }
 impl ElemKind {
-    pub fn get_base_color(&self) -> Color {
+    pub const ALL: [ElemKind; 6] = [
+        ElemKind::Empty,
+        ElemKind::Stone,
+        ElemKind::Sand(SandColor::Yellow),
+        ElemKind::Sand(SandColor::Red),
+        ElemKind::Sand(SandColor::Blue),
+        ElemKind::Sand(SandColor::Green),
+    ];
+
+    pub fn get_base_color(&self, palette: ColorPalette) -> Color {
         match self {
             ElemKind::Empty => EMPTY_COLOR,
-            ElemKind::Sand(sand_color) => { 
-                match sand_color {
-                    SandColor::Yellow => Color::srgba(0.95, 0.82, 0.20, 1.0),    // Bright golden yellow
-                    SandColor::Red => Color::srgba(0.92, 0.25, 0.25, 1.0),       // Vibrant red
-                    SandColor::Blue => Color::srgba(0.20, 0.45, 0.95, 1.0),      // Deeper, more saturated blue
-                    SandColor::Green => Color::srgba(0.25, 0.85, 0.25, 1.0),     // Brighter, more vibrant green
-                }
+            ElemKind::Sand(sand_color) => {
+                let [r, g, b] = sand_color.shade(palette).base;
+                Color::srgba(r, g, b, 1.0)
             },
             ElemKind::Stone => Color::srgba(0.45, 0.45, 0.45, 1.0),
         }
     }
     
-    pub fn get_varied_color_from_position(&self, pos: ElemPos) -> Color {
+    /// Light level given off by the element, up to [`MAX_LIGHT`](lighting::MAX_LIGHT). None of the current elements
+    /// glow, hot ones like fire or lava will
+    pub fn light_emission(&self) -> u8 {
+        match self {
+            ElemKind::Empty | ElemKind::Stone | ElemKind::Sand(_) => 0,
+        }
+    }
+
+    /// How the particles of this kind are shaded
+    pub fn color_variation(&self) -> ColorVariation {
+        match self {
+            ElemKind::Empty => ColorVariation::Flat,
+            ElemKind::Stone => ColorVariation::DepthGradient,
+            ElemKind::Sand(_) => ColorVariation::Noise,
+        }
+    }
+
+    /// A shade of the base colour picked by the low bits of `hash`, only sand has shades
+    pub fn get_varied_color(&self, hash: u64, palette: ColorPalette) -> Color {
         match self {
             ElemKind::Sand(sand_color) => {
-                let mut hasher = DefaultHasher::new();
-                pos.x.hash(&mut hasher);
-                pos.y.hash(&mut hasher);
-                let hash = hasher.finish();
-                    
-                let r_variation = ((hash >> 0) % 32) as f32 / 32.0;
-                let g_variation = ((hash >> 8) % 32) as f32 / 32.0;
-                let b_variation = ((hash >> 16) % 32) as f32 / 32.0;
-                
-                match sand_color {
-                    SandColor::Yellow => {
-                        let r = (0.95f32 + r_variation * 0.15 - 0.075).clamp(0.8, 1.0);
-                        let g = (0.82f32 + g_variation * 0.20 - 0.10).clamp(0.7, 0.95);
-                        let b = (0.20f32 + b_variation * 0.25 - 0.125).clamp(0.1, 0.4);
-                        Color::linear_rgb(r, g, b)
-                    }
-                    SandColor::Red => {
-                        let r = (0.92f32 + r_variation * 0.20 - 0.10).clamp(0.75, 1.0);
-                        let g = (0.25f32 + g_variation * 0.25 - 0.125).clamp(0.15, 0.45);
-                        let b = (0.25f32 + b_variation * 0.20 - 0.10).clamp(0.15, 0.4);
-                        Color::linear_rgb(r, g, b)
-                    }
-                    SandColor::Blue => {
-                        // Deep blue with minimal green component
-                        let r = (0.20f32 + r_variation * 0.15 - 0.075).clamp(0.1, 0.3);
-                        let g = (0.45f32 + g_variation * 0.20 - 0.10).clamp(0.35, 0.6);
-                        let b = (0.95f32 + b_variation * 0.15 - 0.075).clamp(0.8, 1.0);
-                        Color::linear_rgb(r, g, b)
-                    }
-                    SandColor::Green => {
-                        // Vibrant green with minimal blue component
-                        let r = (0.25f32 + r_variation * 0.15 - 0.075).clamp(0.15, 0.35);
-                        let g = (0.85f32 + g_variation * 0.15 - 0.075).clamp(0.75, 0.95);
-                        let b = (0.25f32 + b_variation * 0.15 - 0.075).clamp(0.15, 0.35);
-                        Color::linear_rgb(r, g, b)
-                    }
-                }
+                let variation = [hash, hash >> 5, hash >> 10].map(|bits| (bits % 32) as f32 / 32.0);
+                let shade = sand_color.shade(palette);
+                let [r, g, b] = std::array::from_fn(|i| {
+                    (shade.base[i] + variation[i] * shade.spread[i] - shade.spread[i] / 2.).clamp(shade.min[i], shade.max[i])
+                });
+                Color::linear_rgb(r, g, b)
             }
-            _ => self.get_base_color(),
+            _ => self.get_base_color(palette),
         }
     }
 }
 
-#[derive(Default, Clone, Copy, PartialEq)]
+/// Colour of a [`SandColor`] in a [`ColorPalette`]
+struct SandShade {
+    /// sRGB colour of the element, read as linear when varied per cell
+    base: [f32; 3],
+    /// Width of the per-cell variation of each channel, centred on the base
+    spread: [f32; 3],
+    min: [f32; 3],
+    max: [f32; 3],
+}
+impl SandShade {
+    /// Variation only bounded by the colour range
+    const fn even(base: [f32; 3], spread: f32) -> Self {
+        SandShade { base, spread: [spread; 3], min: [0.; 3], max: [1.; 3] }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
 pub enum SandColor {
     #[default]
     Yellow,
@@ -145,6 +273,72 @@ pub enum SandColor {
     Blue,
     Green
 }
+impl SandColor {
+    fn shade(&self, palette: ColorPalette) -> SandShade {
+        match (palette, self) {
+            (ColorPalette::Standard, SandColor::Yellow) => SandShade {    // Bright golden yellow
+                base: [0.95, 0.82, 0.20], spread: [0.15, 0.20, 0.25], min: [0.8, 0.7, 0.1], max: [1.0, 0.95, 0.4],
+            },
+            (ColorPalette::Standard, SandColor::Red) => SandShade {       // Vibrant red
+                base: [0.92, 0.25, 0.25], spread: [0.20, 0.25, 0.20], min: [0.75, 0.15, 0.15], max: [1.0, 0.45, 0.4],
+            },
+            (ColorPalette::Standard, SandColor::Blue) => SandShade {      // Deep blue with minimal green component
+                base: [0.20, 0.45, 0.95], spread: [0.15, 0.20, 0.15], min: [0.1, 0.35, 0.8], max: [0.3, 0.6, 1.0],
+            },
+            (ColorPalette::Standard, SandColor::Green) => SandShade {     // Vibrant green with minimal blue component
+                base: [0.25, 0.85, 0.25], spread: [0.15; 3], min: [0.15, 0.75, 0.15], max: [0.35, 0.95, 0.35],
+            },
+            // Okabe-Ito colours: yellow, vermillion, blue and bluish green
+            (ColorPalette::Deuteranopia, SandColor::Yellow) => SandShade::even([0.94, 0.89, 0.26], 0.10),
+            (ColorPalette::Deuteranopia, SandColor::Red) => SandShade::even([0.84, 0.37, 0.00], 0.10),
+            (ColorPalette::Deuteranopia, SandColor::Blue) => SandShade::even([0.00, 0.45, 0.70], 0.10),
+            (ColorPalette::Deuteranopia, SandColor::Green) => SandShade::even([0.00, 0.62, 0.45], 0.10),
+            (ColorPalette::Protanopia, SandColor::Yellow) => SandShade::even([0.94, 0.89, 0.26], 0.10),
+            (ColorPalette::Protanopia, SandColor::Red) => SandShade::even([1.00, 0.50, 0.10], 0.10),
+            (ColorPalette::Protanopia, SandColor::Blue) => SandShade::even([0.00, 0.30, 0.60], 0.10),
+            (ColorPalette::Protanopia, SandColor::Green) => SandShade::even([0.00, 0.62, 0.45], 0.10),
+            // Pink and dark navy instead of yellow and blue, red and cyan stay apart
+            (ColorPalette::Tritanopia, SandColor::Yellow) => SandShade::even([0.98, 0.70, 0.75], 0.10),
+            (ColorPalette::Tritanopia, SandColor::Red) => SandShade::even([0.80, 0.10, 0.15], 0.10),
+            (ColorPalette::Tritanopia, SandColor::Blue) => SandShade::even([0.05, 0.20, 0.40], 0.10),
+            (ColorPalette::Tritanopia, SandColor::Green) => SandShade::even([0.35, 0.85, 0.85], 0.10),
+            (ColorPalette::HighContrast, SandColor::Yellow) => SandShade::even([1.00, 0.95, 0.00], 0.),
+            (ColorPalette::HighContrast, SandColor::Red) => SandShade::even([0.85, 0.00, 0.00], 0.),
+            (ColorPalette::HighContrast, SandColor::Blue) => SandShade::even([0.00, 0.15, 0.90], 0.),
+            (ColorPalette::HighContrast, SandColor::Green) => SandShade::even([0.00, 1.00, 0.40], 0.),
+        }
+    }
+
+    /// Whether the cell at `pos` belongs to the texture drawn over this colour when
+    /// [`Settings::sand_patterns`](crate::settings::Settings::sand_patterns) is on: yellow is plain, red is
+    /// striped horizontally, blue dotted and green striped diagonally
+    pub fn has_pattern_at(&self, pos: ElemPos) -> bool {
+        match self {
+            SandColor::Yellow => false,
+            SandColor::Red => pos.y.is_multiple_of(3),
+            SandColor::Blue => pos.x.is_multiple_of(3) && pos.y.is_multiple_of(3),
+            SandColor::Green => (pos.x + pos.y).is_multiple_of(4),
+        }
+    }
+}
+
+impl ElemKind {
+    /// Lowercase identifier used in machine readable output
+    pub fn name(&self) -> &'static str {
+        match self {
+            ElemKind::Empty => "empty",
+            ElemKind::Stone => "stone",
+            ElemKind::Sand(SandColor::Yellow) => "sand_yellow",
+            ElemKind::Sand(SandColor::Red) => "sand_red",
+            ElemKind::Sand(SandColor::Blue) => "sand_blue",
+            ElemKind::Sand(SandColor::Green) => "sand_green",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ElemKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
 
 impl Display for ElemKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -163,7 +357,7 @@ impl Display for ElemKind {
     }
 }
 
-#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub struct ElemPos{
     pub x: u32,
     pub y: u32
@@ -172,21 +366,18 @@ impl ElemPos {
     pub fn new(x: u32, y: u32) -> Self {
         ElemPos{ x, y }
     }
-    pub fn in_bounds(&self) -> bool {
-        if self.y < GRID_SIZE.height 
-        && self.x < GRID_SIZE.width { true } else { false }
+    pub fn in_bounds(&self, size: GridSize) -> bool {
+        self.y < size.height 
+        && self.x < size.width
     }
-    pub fn in_border_bottom(&self) -> bool {
-        if self.y < GRID_SIZE.height - 1 { true }
-        else { false }
+    pub fn in_border_bottom(&self, size: GridSize) -> bool {
+        self.y < size.height - 1
     }
     pub fn in_border_left(&self) -> bool {
-        if self.x > 0 { true }
-        else { false }
+        self.x > 0
     }
-    pub fn in_border_right(&self) -> bool {
-        if self.x < GRID_SIZE.width - 1 { true }
-        else { false }
+    pub fn in_border_right(&self, size: GridSize) -> bool {
+        self.x < size.width - 1
     }
     /*
     pub fn get_inbound_coords_within_sq_radius(&self, radius: u32) -> Vec<ElemPos> {
@@ -0,0 +1,85 @@
+use std::{fs, path::{Path, PathBuf}};
+use bevy::{asset::Assets, color::ColorToPacked, ecs::system::{Res, Single}, image::Image, log::{error, info}};
+use image::{imageops::{self, FilterType}, ImageResult, RgbaImage};
+use crate::{actions::{Action, ActionInput}, game::sandworld::{draw_image::GridStyle, lighting::LightMap, simulation_control::SimulationControl, wall_layer::WallCells, ElemPos, GridCells, GridImage, GRID_SCALE}, settings::{LightingMode, Settings}, utils::helper_utils::timestamp};
+
+const SCREENSHOT_DIR: &str = "screenshots";
+
+/// Resolution of an exported grid image
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportScale {
+    /// One pixel per cell
+    Native,
+    /// Nearest-neighbour upscaled by [`GRID_SCALE`], as the grid is shown in game
+    Upscaled,
+}
+impl ExportScale {
+    fn factor(&self) -> u32 {
+        match self {
+            ExportScale::Native => 1,
+            ExportScale::Upscaled => GRID_SCALE as u32,
+        }
+    }
+}
+
+/// Renders the grid the same way [`draw_image`](super::draw_image::draw_image) does, without needing a Bevy [`Image`]
+pub fn grid_to_rgba(grid_cells: &GridCells, wall_cells: &WallCells, style: GridStyle, tick: u64) -> RgbaImage {
+    let light_map = (style.lighting != LightingMode::Off).then(|| LightMap::compute(grid_cells)).flatten();
+    RgbaImage::from_fn(grid_cells.size().width, grid_cells.size().height, |x, y| {
+        let pos = ElemPos::new(x, y);
+        let mut color = style.pixel_color(grid_cells, wall_cells, pos, tick);
+        if let Some(light_map) = &light_map {
+            color = light_map.lit_color(color, pos, style.lighting);
+        }
+        image::Rgba(color.to_srgba().to_u8_array())
+    })
+}
+
+/// Copies the pixels of an `Rgba8UnormSrgb` image such as the one behind [`GridImage`]
+pub fn image_to_rgba(image: &Image) -> Option<RgbaImage> {
+    RgbaImage::from_raw(image.width(), image.height(), image.data.clone()?)
+}
+
+pub fn scale_image(image: &RgbaImage, scale: ExportScale) -> RgbaImage {
+    match scale {
+        ExportScale::Native => image.clone(),
+        ExportScale::Upscaled => imageops::resize(
+            image,
+            image.width() * scale.factor(),
+            image.height() * scale.factor(),
+            FilterType::Nearest,
+        ),
+    }
+}
+
+/// Writes the image as `<prefix>-<timestamp>.png` into `dir` and returns the path
+pub fn export_png(image: &RgbaImage, dir: &Path, prefix: &str, scale: ExportScale) -> ImageResult<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{prefix}-{}.png", timestamp()));
+
+    scale_image(image, scale).save(&path)?;
+    Ok(path)
+}
+
+/// [`Action::Screenshot`] saves the current grid image at native resolution, upscaled while Shift is held
+pub fn screenshot_on_key(
+    actions: ActionInput,
+    handle: Res<GridImage>,
+    images: Res<Assets<Image>>,
+    grid: Single<(&GridCells, &WallCells)>,
+    settings: Res<Settings>,
+    control: Res<SimulationControl>,
+) {
+    if !actions.just_pressed(Action::Screenshot) { return }
+
+    let scale = if actions.shift() { ExportScale::Upscaled } else { ExportScale::Native };
+    let image = images
+        .get(&handle.0)
+        .and_then(image_to_rgba)
+        .unwrap_or_else(|| grid_to_rgba(grid.0, grid.1, GridStyle::from_settings(&settings), control.tick));
+
+    match export_png(&image, Path::new(SCREENSHOT_DIR), "sandrisso", scale) {
+        Ok(path) => info!("Saved screenshot to {}", path.display()),
+        Err(err) => error!("Could not save screenshot: {err}"),
+    }
+}
@@ -0,0 +1,87 @@
+use std::{fmt::Display, io, path::Path};
+use bevy::color::ColorToPacked;
+use image::{imageops::{self, FilterType}, ImageReader, RgbaImage};
+use crate::{game::sandworld::{Elem, ElemKind, ElemPos, GridCells, GridSize}, settings::ColorPalette};
+
+/// Pixels with a lower alpha are imported as [`ElemKind::Empty`]
+const OPAQUE_ALPHA_THRESHOLD: u8 = 128;
+
+/// How an image that does not match the grid size is fitted onto it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImportFit {
+    /// Nearest-neighbour scaled to exactly the grid size
+    Resize,
+    /// Taken from the top-left corner, missing cells stay empty
+    Crop,
+}
+
+#[derive(Debug)]
+pub enum PngImportError {
+    Io(io::Error),
+    Decode(image::ImageError),
+}
+impl Display for PngImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngImportError::Io(err) => write!(f, "could not read the image: {err}"),
+            PngImportError::Decode(err) => write!(f, "could not decode the image: {err}"),
+        }
+    }
+}
+impl std::error::Error for PngImportError {}
+impl From<io::Error> for PngImportError {
+    fn from(err: io::Error) -> Self {
+        PngImportError::Io(err)
+    }
+}
+impl From<image::ImageError> for PngImportError {
+    fn from(err: image::ImageError) -> Self {
+        PngImportError::Decode(err)
+    }
+}
+
+/// Reads a PNG and turns it into a grid of `size` by matching every pixel to the closest element colour
+pub fn load_png_world(path: &Path, fit: ImportFit, size: GridSize) -> Result<GridCells, PngImportError> {
+    let image = ImageReader::open(path)?
+        .with_guessed_format()?
+        .decode()?
+        .to_rgba8();
+
+    Ok(grid_from_rgba(&image, fit, size))
+}
+
+pub fn grid_from_rgba(image: &RgbaImage, fit: ImportFit, size: GridSize) -> GridCells {
+    let fitted = match fit {
+        ImportFit::Resize => imageops::resize(image, size.width, size.height, FilterType::Nearest),
+        ImportFit::Crop => imageops::crop_imm(
+            image,
+            0,
+            0,
+            image.width().min(size.width),
+            image.height().min(size.height),
+        ).to_image(),
+    };
+
+    let mut grid_cells = GridCells::new_sized(size);
+    for (x, y, pixel) in fitted.enumerate_pixels() {
+        let pos = ElemPos::new(x, y);
+        grid_cells.set_elem_at(pos, Elem::new(nearest_elem_kind(pixel.0), false).seeded_at(pos));
+    }
+    grid_cells
+}
+
+/// Transparent pixels become empty, opaque ones the element whose [`ElemKind::get_base_color`] in the standard palette is closest in sRGB
+pub fn nearest_elem_kind(rgba: [u8; 4]) -> ElemKind {
+    if rgba[3] < OPAQUE_ALPHA_THRESHOLD { return ElemKind::Empty }
+
+    ElemKind::ALL
+        .into_iter()
+        .filter(|kind| *kind != ElemKind::Empty)
+        .min_by_key(|kind| {
+            let base = kind.get_base_color(ColorPalette::Standard).to_srgba().to_u8_array();
+            (0..3)
+                .map(|i| (base[i] as i32 - rgba[i] as i32).pow(2))
+                .sum::<i32>()
+        })
+        .unwrap_or(ElemKind::Empty)
+}
@@ -0,0 +1,145 @@
+use std::{collections::VecDeque, fs::{self, File}, io::BufWriter, path::{Path, PathBuf}, time::Duration};
+use bevy::{asset::Assets, ecs::{resource::Resource, system::{Res, ResMut}}, image::Image, log::{error, info}, tasks::AsyncComputeTaskPool, time::{Fixed, Time}};
+use image::{codecs::gif::{GifEncoder, Repeat}, Delay, Frame, ImageResult, RgbaImage};
+use crate::{actions::{Action, ActionInput}, game::sandworld::{png_export::image_to_rgba, GridImage}, settings::Settings, utils::helper_utils::timestamp};
+
+const RECORDING_DIR: &str = "recordings";
+/// Keeps every other frame
+pub const DEFAULT_FRAME_SKIP: u32 = 1;
+pub const DEFAULT_MAX_FRAMES: usize = 300;
+/// Values offered by the settings screen
+pub const FRAME_SKIP_STEPS: [u32; 4] = [0, 1, 2, 3];
+pub const MAX_FRAMES_STEPS: [usize; 4] = [150, 300, 600, 1200];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordingFormat {
+    Gif,
+    PngSequence,
+}
+
+/// Captures every rendered grid frame into a ring buffer while recording, encoding happens on a background task once stopped
+#[derive(Resource)]
+pub struct Recorder {
+    /// Number of frames dropped between two captured ones
+    pub frame_skip: u32,
+    /// Oldest frames are dropped once the buffer holds that many
+    pub max_frames: usize,
+    format: Option<RecordingFormat>,
+    frames: VecDeque<RgbaImage>,
+    skipped: u32,
+}
+impl Default for Recorder {
+    fn default() -> Self {
+        Recorder {
+            frame_skip: DEFAULT_FRAME_SKIP,
+            max_frames: DEFAULT_MAX_FRAMES,
+            format: None,
+            frames: VecDeque::new(),
+            skipped: 0,
+        }
+    }
+}
+impl Recorder {
+    pub fn is_recording(&self) -> bool {
+        self.format.is_some()
+    }
+
+    pub fn start(&mut self, format: RecordingFormat) {
+        self.format = Some(format);
+        self.frames.clear();
+        self.skipped = 0;
+    }
+
+    /// Stops recording and hands out the captured frames
+    pub fn stop(&mut self) -> Option<(RecordingFormat, Vec<RgbaImage>)> {
+        let format = self.format.take()?;
+        Some((format, self.frames.drain(..).collect()))
+    }
+
+    /// Offers a frame, which is kept or dropped according to `frame_skip`
+    pub fn push_frame(&mut self, frame: impl FnOnce() -> Option<RgbaImage>) {
+        if !self.is_recording() { return }
+
+        if self.skipped < self.frame_skip {
+            self.skipped += 1;
+            return
+        }
+        self.skipped = 0;
+
+        if let Some(frame) = frame() {
+            if self.frames.len() >= self.max_frames {
+                self.frames.pop_front();
+            }
+            self.frames.push_back(frame);
+        }
+    }
+}
+
+pub fn encode_gif(frames: Vec<RgbaImage>, path: &Path, frame_time: Duration) -> ImageResult<()> {
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let delay = Delay::from_saturating_duration(frame_time);
+    encoder.encode_frames(frames.into_iter().map(|frame| Frame::from_parts(frame, 0, 0, delay)))
+}
+
+/// Writes `frame-00000.png`, `frame-00001.png`... into `dir`
+pub fn encode_png_sequence(frames: Vec<RgbaImage>, dir: &Path) -> ImageResult<()> {
+    fs::create_dir_all(dir)?;
+    for (i, frame) in frames.iter().enumerate() {
+        frame.save(dir.join(format!("frame-{i:05}.png")))?;
+    }
+    Ok(())
+}
+
+/// Copies the grid image into the [`Recorder`], runs right after [`draw_image`](super::draw_image::draw_image)
+pub fn capture_recording_frame(
+    mut recorder: ResMut<Recorder>,
+    handle: Res<GridImage>,
+    images: Res<Assets<Image>>,
+) {
+    recorder.push_frame(|| images.get(&handle.0).and_then(image_to_rgba));
+}
+
+/// [`Action::ToggleRecording`] starts and stops a GIF recording, or a PNG sequence while Shift is held.
+/// The frame skip and buffer size are taken from the [`Settings`] when a recording starts
+pub fn toggle_recording(
+    actions: ActionInput,
+    mut recorder: ResMut<Recorder>,
+    settings: Res<Settings>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    if !actions.just_pressed(Action::ToggleRecording) { return }
+
+    if let Some((format, frames)) = recorder.stop() {
+        let frame_time = fixed_time.timestep() * (recorder.frame_skip + 1);
+        info!("Stopped recording, encoding {} frames", frames.len());
+
+        AsyncComputeTaskPool::get()
+            .spawn(async move { save_recording(format, frames, frame_time) })
+            .detach();
+    } else {
+        let format = if actions.shift() { RecordingFormat::PngSequence } else { RecordingFormat::Gif };
+        recorder.frame_skip = settings.recording_frame_skip;
+        recorder.max_frames = settings.recording_max_frames.max(1);
+        recorder.start(format);
+        info!("Started recording ({format:?})");
+    }
+}
+
+fn save_recording(format: RecordingFormat, frames: Vec<RgbaImage>, frame_time: Duration) {
+    let name = format!("sandrisso-{}", timestamp());
+    let path: PathBuf = Path::new(RECORDING_DIR).join(&name);
+
+    let result = fs::create_dir_all(RECORDING_DIR)
+        .map_err(image::ImageError::from)
+        .and_then(|_| match format {
+            RecordingFormat::Gif => encode_gif(frames, &path.with_extension("gif"), frame_time),
+            RecordingFormat::PngSequence => encode_png_sequence(frames, &path),
+        });
+
+    match result {
+        Ok(()) => info!("Saved recording {name}"),
+        Err(err) => error!("Could not save recording {name}: {err}"),
+    }
+}
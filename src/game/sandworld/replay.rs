@@ -0,0 +1,366 @@
+use std::{collections::VecDeque, fmt::Display, fs, io, path::{Path, PathBuf}};
+use bevy::{ecs::{event::{Event, EventReader, EventWriter}, resource::Resource, system::{Res, ResMut, Single}}, log::{error, info, warn}};
+use crate::{actions::{Action, ActionInput}, game::sandworld::{edit_history::{EditHistory, HistoryMode}, simulation_control::SimulationControl, user_element_interraction::{PaintLayer, UserEdit, UserSelectedElements}, wall_layer::WallCells, world_file::{byte_to_kind, decode_world, encode_world, kind_to_byte, ByteReader, WorldFileError}, ElemPos, GridCells}, utils::helper_utils::timestamp};
+
+const MAGIC: [u8; 4] = *b"SNDR";
+pub const REPLAY_FILE_VERSION: u16 = 2;
+pub const REPLAY_FILE_EXTENSION: &str = "sandr";
+const REPLAY_DIR: &str = "replays";
+
+/// A starting world and wall (both stored as world files) and every [`UserEdit`] applied on top of them, tagged with its tick
+pub struct Replay {
+    pub world: Vec<u8>,
+    /// The [`WallCells`] as a world file, see [`WallCells::to_grid_cells`]
+    pub wall: Vec<u8>,
+    pub edits: Vec<(u64, UserEdit)>,
+}
+
+/// Asks for the replay at the given path to be played back instead of live input
+#[derive(Event)]
+pub struct PlayReplay(pub PathBuf);
+
+/// Records [`UserEdit`]s while recording and feeds them back during playback
+#[derive(Resource, Default)]
+pub struct ReplayState {
+    recording: Option<Replay>,
+    playback: VecDeque<(u64, UserEdit)>,
+    playing: bool,
+    last_saved: Option<PathBuf>,
+}
+impl ReplayState {
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Starts a recording from the current world and wall, the current selection and history mode are recorded as the first edits.
+    /// The history is cleared, as undoing a stroke from before the recording could not be played back
+    pub fn start_recording(&mut self, grid_cells: &GridCells, wall_cells: &WallCells, control: &SimulationControl, selection: &UserSelectedElements, history: &mut EditHistory) {
+        history.clear();
+        self.recording = Some(Replay {
+            world: encode_world(grid_cells, control.seed, control.tick),
+            wall: encode_world(&wall_cells.to_grid_cells(), control.seed, control.tick),
+            edits: vec![
+                (control.tick, UserEdit::SelectElement(selection.kind)),
+                (control.tick, UserEdit::SetRadius(selection.radius)),
+                (control.tick, UserEdit::SetLayer(selection.layer)),
+                (control.tick, UserEdit::SetHistoryMode(history.mode)),
+            ],
+        });
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        self.recording.take()
+    }
+
+    /// Stops the recording and saves it into `replays/`, where [`Action::PlayReplay`] finds it
+    fn save_recording(&mut self) {
+        let Some(recorded) = self.stop_recording() else { return };
+
+        let path = Path::new(REPLAY_DIR).join(format!("replay-{}.{REPLAY_FILE_EXTENSION}", timestamp()));
+        match save_replay(&path, &recorded) {
+            Ok(()) => {
+                info!("Saved replay with {} edits to {}", recorded.edits.len(), path.display());
+                self.last_saved = Some(path);
+            }
+            Err(err) => error!("Could not save replay to {}: {err}", path.display()),
+        }
+    }
+
+    /// Called once the grid was replaced by another world, which the recorded or played back edits do not apply to.
+    /// Playback stops, while a recording is saved up to here and goes on as a new replay starting from the new world
+    pub fn world_replaced(&mut self, grid_cells: &GridCells, wall_cells: &WallCells, control: &mut SimulationControl, selection: &UserSelectedElements, history: &mut EditHistory) {
+        if self.playing {
+            self.stop_playback();
+            control.run_until = None;
+            info!("Replay stopped, the world was replaced");
+        }
+        if self.recording.is_some() {
+            self.save_recording();
+            self.start_recording(grid_cells, wall_cells, control, selection, history);
+            info!("Started recording a new replay from the replaced world");
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, edit: UserEdit) {
+        if let Some(replay) = &mut self.recording {
+            replay.edits.push((tick, edit));
+        }
+    }
+
+    pub fn start_playback(&mut self, edits: Vec<(u64, UserEdit)>) {
+        self.recording = None;
+        self.playback = edits.into();
+        self.playing = true;
+    }
+
+    pub fn stop_playback(&mut self) {
+        self.playback.clear();
+        self.playing = false;
+    }
+
+    /// Pops every edit due at or before `tick`
+    fn take_due(&mut self, tick: u64) -> Vec<UserEdit> {
+        let mut due = Vec::new();
+        while let Some((_, edit)) = self.playback.pop_front_if(|(edit_tick, _)| *edit_tick <= tick) {
+            due.push(edit);
+        }
+        due
+    }
+
+    fn next_tick(&self) -> Option<u64> {
+        self.playback.front().map(|(tick, _)| *tick)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayFileError {
+    Io(io::Error),
+    NotAReplayFile,
+    UnsupportedVersion(u16),
+    Truncated,
+    InvalidEdit(u8),
+    InvalidValue { tag: u8, value: u8 },
+    World(WorldFileError),
+}
+impl Display for ReplayFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayFileError::Io(err) => write!(f, "could not access the replay file: {err}"),
+            ReplayFileError::NotAReplayFile => write!(f, "the file is not a replay file"),
+            ReplayFileError::UnsupportedVersion(version) => write!(f, "replay file version {version} is newer than the supported version {REPLAY_FILE_VERSION}"),
+            ReplayFileError::Truncated => write!(f, "the replay file ends unexpectedly"),
+            ReplayFileError::InvalidEdit(tag) => write!(f, "the replay file contains an unknown edit {tag:#04x}"),
+            ReplayFileError::InvalidValue { tag, value } => write!(f, "the replay file contains an edit {tag:#04x} with an unknown value {value:#04x}"),
+            ReplayFileError::World(err) => write!(f, "the replay file holds an invalid world: {err}"),
+        }
+    }
+}
+impl std::error::Error for ReplayFileError {}
+impl From<io::Error> for ReplayFileError {
+    fn from(err: io::Error) -> Self {
+        ReplayFileError::Io(err)
+    }
+}
+impl From<WorldFileError> for ReplayFileError {
+    fn from(err: WorldFileError) -> Self {
+        match err {
+            WorldFileError::Truncated => ReplayFileError::Truncated,
+            err => ReplayFileError::World(err),
+        }
+    }
+}
+
+/// Layout (little endian): magic, version `u16`, world file length `u32`, world file, wall world file length `u32`,
+/// wall world file, edit count `u32`, then every edit as tick `u64`, tag `u8` and the tag's payload.
+/// Version 1 files have no wall, it is read as empty
+pub fn encode_replay(replay: &Replay) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&REPLAY_FILE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(replay.world.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&replay.world);
+    bytes.extend_from_slice(&(replay.wall.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&replay.wall);
+    bytes.extend_from_slice(&(replay.edits.len() as u32).to_le_bytes());
+
+    for (tick, edit) in &replay.edits {
+        bytes.extend_from_slice(&tick.to_le_bytes());
+        match edit {
+            UserEdit::SelectElement(kind) => {
+                bytes.push(0);
+                bytes.push(kind_to_byte(*kind));
+            }
+            UserEdit::SetRadius(radius) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&radius.to_le_bytes());
+            }
+            UserEdit::Paint { from, to } => {
+                bytes.push(2);
+                bytes.push(from.is_some() as u8);
+                let from = from.unwrap_or(*to);
+                for coord in [from.x, from.y, to.x, to.y] {
+                    bytes.extend_from_slice(&coord.to_le_bytes());
+                }
+            }
+            UserEdit::EndStroke => bytes.push(3),
+            UserEdit::Undo => bytes.push(4),
+            UserEdit::Redo => bytes.push(5),
+            UserEdit::SetHistoryMode(mode) => {
+                bytes.push(6);
+                bytes.push(match mode {
+                    HistoryMode::Diff => 0,
+                    HistoryMode::Snapshot => 1,
+                });
+            }
+            UserEdit::SetLayer(layer) => {
+                bytes.push(7);
+                bytes.push(match layer {
+                    PaintLayer::World => 0,
+                    PaintLayer::Wall => 1,
+                });
+            }
+        }
+    }
+    bytes
+}
+
+pub fn decode_replay(bytes: &[u8]) -> Result<Replay, ReplayFileError> {
+    let mut reader = ByteReader::new(bytes);
+
+    if reader.take(4).map_err(|_| ReplayFileError::NotAReplayFile)? != MAGIC {
+        return Err(ReplayFileError::NotAReplayFile)
+    }
+    let version = reader.u16()?;
+    if !(1..=REPLAY_FILE_VERSION).contains(&version) {
+        return Err(ReplayFileError::UnsupportedVersion(version))
+    }
+
+    let world_len = reader.u32()? as usize;
+    let world = reader.take(world_len)?.to_vec();
+    decode_world(&world)?;
+
+    let wall = if version >= 2 {
+        let wall_len = reader.u32()? as usize;
+        reader.take(wall_len)?.to_vec()
+    } else {
+        encode_world(&WallCells::default().to_grid_cells(), 0, 0)
+    };
+    decode_world(&wall)?;
+
+    let edit_count = reader.u32()?;
+    let mut edits = Vec::new();
+    for _ in 0..edit_count {
+        let tick = reader.u64()?;
+        let edit = match reader.u8()? {
+            0 => {
+                let byte = reader.u8()?;
+                UserEdit::SelectElement(byte_to_kind(byte).ok_or(WorldFileError::InvalidCell(byte))?)
+            }
+            1 => UserEdit::SetRadius(reader.u32()?),
+            2 => {
+                let has_from = reader.u8()? != 0;
+                let from = ElemPos::new(reader.u32()?, reader.u32()?);
+                let to = ElemPos::new(reader.u32()?, reader.u32()?);
+                UserEdit::Paint { from: has_from.then_some(from), to }
+            }
+            3 => UserEdit::EndStroke,
+            4 => UserEdit::Undo,
+            5 => UserEdit::Redo,
+            6 => UserEdit::SetHistoryMode(match reader.u8()? {
+                0 => HistoryMode::Diff,
+                1 => HistoryMode::Snapshot,
+                mode => return Err(ReplayFileError::InvalidValue { tag: 6, value: mode }),
+            }),
+            7 => UserEdit::SetLayer(match reader.u8()? {
+                0 => PaintLayer::World,
+                1 => PaintLayer::Wall,
+                layer => return Err(ReplayFileError::InvalidValue { tag: 7, value: layer }),
+            }),
+            tag => return Err(ReplayFileError::InvalidEdit(tag)),
+        };
+        edits.push((tick, edit));
+    }
+    Ok(Replay { world, wall, edits })
+}
+
+pub fn save_replay(path: &Path, replay: &Replay) -> Result<(), ReplayFileError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, encode_replay(replay))?;
+    Ok(())
+}
+
+pub fn load_replay(path: &Path) -> Result<Replay, ReplayFileError> {
+    decode_replay(&fs::read(path)?)
+}
+
+/// [`Action::ToggleReplayRecording`] starts and stops recording a replay into `replays/`, [`Action::PlayReplay`] plays back the last saved one
+pub fn user_controls_replay(
+    actions: ActionInput,
+    mut replay: ResMut<ReplayState>,
+    grid: Single<(&GridCells, &WallCells)>,
+    control: Res<SimulationControl>,
+    selection: Res<UserSelectedElements>,
+    mut history: ResMut<EditHistory>,
+    mut play_events: EventWriter<PlayReplay>,
+) {
+    if actions.just_pressed(Action::ToggleReplayRecording) && !replay.is_playing() {
+        if replay.recording.is_some() {
+            replay.save_recording();
+        } else {
+            let (grid_cells, wall_cells) = *grid;
+            replay.start_recording(grid_cells, wall_cells, &control, &selection, &mut history);
+            info!("Started recording a replay");
+        }
+    }
+
+    if actions.just_pressed(Action::PlayReplay) {
+        match replay.last_saved.clone() {
+            Some(path) => { play_events.write(PlayReplay(path)); },
+            None => warn!("No replay recorded yet"),
+        }
+    }
+}
+
+/// Loads the replay's starting world and wall and queues its edits for [`feed_replay_edits`]
+pub fn start_replay_on_event(
+    mut events: EventReader<PlayReplay>,
+    mut replay: ResMut<ReplayState>,
+    grid: Single<(&mut GridCells, &mut WallCells)>,
+    mut control: ResMut<SimulationControl>,
+    mut history: ResMut<EditHistory>,
+) {
+    let (mut grid_cells, mut wall_cells) = grid.into_inner();
+    for PlayReplay(path) in events.read() {
+        let loaded = load_replay(path)
+            .and_then(|loaded| Ok((decode_world(&loaded.world)?, decode_world(&loaded.wall)?, loaded.edits)));
+
+        match loaded {
+            Ok((world, wall, edits)) => {
+                let size = grid_cells.size();
+                if !world.fits_grid(size) {
+                    warn!("Replay {} was recorded on a {}x{} grid, it was cropped to the current grid", path.display(), world.meta.width, world.meta.height);
+                }
+                *grid_cells = world.to_grid_cells(size);
+                *wall_cells = WallCells::from_grid_cells(&wall.to_grid_cells(size));
+                control.tick = world.meta.tick;
+                control.seed = world.meta.seed;
+                history.clear();
+                replay.start_playback(edits);
+                control.run_until = replay.next_tick();
+
+                info!("Playing replay {}", path.display());
+            }
+            Err(err) => error!("Could not play replay {}: {err}", path.display()),
+        }
+    }
+}
+
+/// During playback sends the edits due at the current tick and holds the simulation at the tick of the next one
+pub fn feed_replay_edits(
+    mut replay: ResMut<ReplayState>,
+    mut control: ResMut<SimulationControl>,
+    mut edits: EventWriter<UserEdit>,
+) {
+    if !replay.is_playing() { return }
+
+    edits.write_batch(replay.take_due(control.tick));
+    control.run_until = replay.next_tick();
+
+    if control.run_until.is_none() {
+        replay.stop_playback();
+        info!("Replay finished");
+    }
+}
+
+/// Run condition for the live input systems, which are replaced by [`feed_replay_edits`] during playback
+pub fn live_input(replay: Res<ReplayState>) -> bool {
+    !replay.is_playing()
+}
+
+pub fn reset_replay(mut replay: ResMut<ReplayState>, mut control: ResMut<SimulationControl>) {
+    replay.stop_recording();
+    replay.stop_playback();
+    control.run_until = None;
+}
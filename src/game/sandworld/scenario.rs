@@ -0,0 +1,140 @@
+use std::{fmt::Display, fs, io, path::Path};
+use crate::game::sandworld::{main_interaction::simulation_step, Elem, ElemKind, ElemPos, GridCells, SandColor, GRID_SIZE};
+
+const MAP_SEPARATOR: &str = "---";
+
+/// A small hand-drawn map placed on an otherwise empty grid, simulated for a fixed number of ticks
+///
+/// Scenario files hold `key = value` lines (`ticks`, optionally `origin = x y`), a `---` line and the map,
+/// one character per cell as given by [`kind_to_char`]. Header lines starting with `#` are comments
+pub struct Scenario {
+    pub ticks: u64,
+    pub origin: ElemPos,
+    pub width: u32,
+    pub height: u32,
+    pub grid: GridCells,
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Syntax { line: usize, message: String },
+}
+impl Display for ScenarioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "could not read the scenario: {err}"),
+            ScenarioError::Syntax { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+impl std::error::Error for ScenarioError {}
+impl From<io::Error> for ScenarioError {
+    fn from(err: io::Error) -> Self {
+        ScenarioError::Io(err)
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ScenarioError> {
+        let syntax = |line: usize, message: String| ScenarioError::Syntax { line: line + 1, message };
+
+        let mut ticks = None;
+        let mut origin = ElemPos::new(0, 0);
+        let mut lines = text.lines().enumerate();
+
+        for (i, line) in lines.by_ref() {
+            let line = line.trim();
+            if line == MAP_SEPARATOR { break }
+            if line.is_empty() || line.starts_with('#') { continue }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| syntax(i, format!("expected `key = value`, found `{line}`")))?;
+            match key.trim() {
+                "ticks" => ticks = Some(value.trim().parse().map_err(|_| syntax(i, format!("invalid tick count `{}`", value.trim())))?),
+                "origin" => {
+                    let coords: Vec<u32> = value.split_whitespace().filter_map(|coord| coord.parse().ok()).collect();
+                    let [x, y] = coords[..] else { return Err(syntax(i, "origin needs two coordinates".into())) };
+                    origin = ElemPos::new(x, y);
+                }
+                key => return Err(syntax(i, format!("unknown key `{key}`"))),
+            }
+        }
+
+        let map: Vec<(usize, &str)> = lines.map(|(i, line)| (i, line.trim_end())).filter(|(_, line)| !line.is_empty()).collect();
+        let height = map.len() as u32;
+        let width = map.iter().map(|(_, line)| line.chars().count() as u32).max().unwrap_or(0);
+        if origin.x + width > GRID_SIZE.width || origin.y + height > GRID_SIZE.height {
+            return Err(syntax(0, format!("a {width}x{height} map at {},{} does not fit the grid", origin.x, origin.y)));
+        }
+
+        let mut grid = GridCells::new_empty();
+        for (y, (i, line)) in map.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                let kind = char_to_kind(c).ok_or_else(|| syntax(*i, format!("unknown cell `{c}`")))?;
+                let pos = ElemPos::new(origin.x + x as u32, origin.y + y as u32);
+                grid.set_elem_at(pos, Elem::new(kind, false).seeded_at(pos));
+            }
+        }
+
+        Ok(Scenario {
+            ticks: ticks.ok_or_else(|| syntax(0, "missing `ticks`".into()))?,
+            origin,
+            width,
+            height,
+            grid,
+        })
+    }
+
+    /// Simulates the scenario from tick 0 and returns the final grid
+    pub fn run(&self) -> GridCells {
+        let mut grid = self.grid.clone();
+        for tick in 0..self.ticks {
+            simulation_step(&mut grid, tick);
+        }
+        grid
+    }
+
+    /// The map window of the given grid, in the same format as the scenario map
+    pub fn render(&self, grid: &GridCells) -> String {
+        let mut text = String::new();
+        for y in self.origin.y..self.origin.y + self.height {
+            for x in self.origin.x..self.origin.x + self.width {
+                text.push(kind_to_char(grid.get_elem_at(ElemPos::new(x, y)).unwrap().kind));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Number of non-empty cells that ended up outside the map window
+    pub fn cells_outside_window(&self, grid: &GridCells) -> usize {
+        let inside = |pos: ElemPos| {
+            (self.origin.x..self.origin.x + self.width).contains(&pos.x)
+            && (self.origin.y..self.origin.y + self.height).contains(&pos.y)
+        };
+
+        (0..GRID_SIZE.height)
+            .flat_map(|y| (0..GRID_SIZE.width).map(move |x| ElemPos::new(x, y)))
+            .filter(|pos| !inside(*pos) && grid.get_elem_at(*pos).unwrap().kind != ElemKind::Empty)
+            .count()
+    }
+}
+
+pub fn kind_to_char(kind: ElemKind) -> char {
+    match kind {
+        ElemKind::Empty => '.',
+        ElemKind::Stone => '#',
+        ElemKind::Sand(SandColor::Yellow) => 'y',
+        ElemKind::Sand(SandColor::Red) => 'r',
+        ElemKind::Sand(SandColor::Blue) => 'b',
+        ElemKind::Sand(SandColor::Green) => 'g',
+    }
+}
+
+pub fn char_to_kind(c: char) -> Option<ElemKind> {
+    ElemKind::ALL.into_iter().find(|kind| kind_to_char(*kind) == c)
+}
@@ -0,0 +1,116 @@
+use bevy::{ecs::{resource::Resource, system::ResMut}, log::info};
+use crate::actions::{Action, ActionInput};
+
+/// Speed multipliers selectable with the `+` and `-` keys
+pub const SPEED_STEPS: [f32; 6] = [0.25, 0.5, 1., 2., 4., 8.];
+const DEFAULT_SPEED_INDEX: usize = 2;
+
+/// Controls how many simulation steps the [`main_interaction_loop`](super::main_interaction::main_interaction_loop) runs per fixed tick
+///
+/// Painting is not affected by it, so a scene can be set up while paused and released afterwards
+#[derive(Resource)]
+pub struct SimulationControl {
+    pub paused: bool,
+    /// Number of simulation steps run since the world was created
+    pub tick: u64,
+    /// Seed of the world, stored in world files so randomized behaviour can be reproduced
+    pub seed: u64,
+    /// When set, no steps are run past this tick, even while running fast
+    pub run_until: Option<u64>,
+    speed_index: usize,
+    step_requested: bool,
+    accumulator: f32,
+}
+impl Default for SimulationControl {
+    fn default() -> Self {
+        SimulationControl {
+            paused: false,
+            tick: 0,
+            seed: rand::random(),
+            run_until: None,
+            speed_index: DEFAULT_SPEED_INDEX,
+            step_requested: false,
+            accumulator: 0.
+        }
+    }
+}
+impl SimulationControl {
+    pub fn speed(&self) -> f32 {
+        SPEED_STEPS[self.speed_index]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(SPEED_STEPS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+    /// Selects the speed step closest to the given multiplier
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed_index = (0..SPEED_STEPS.len())
+            .min_by(|a, b| (SPEED_STEPS[*a] - speed).abs().total_cmp(&(SPEED_STEPS[*b] - speed).abs()))
+            .unwrap_or(DEFAULT_SPEED_INDEX);
+    }
+
+    /// Runs exactly one step on the next fixed tick, pausing the simulation if it was running
+    pub fn request_step(&mut self) {
+        self.paused = true;
+        self.step_requested = true;
+    }
+
+    /// Number of simulation steps to run in the current fixed tick
+    pub fn take_steps(&mut self) -> u32 {
+        let steps = self.take_unlimited_steps();
+        match self.run_until {
+            Some(limit) => steps.min(limit.saturating_sub(self.tick) as u32),
+            None => steps,
+        }
+    }
+
+    fn take_unlimited_steps(&mut self) -> u32 {
+        if self.paused {
+            if self.step_requested {
+                self.step_requested = false;
+                1
+            } else { 0 }
+        } else {
+            self.accumulator += self.speed();
+            let steps = self.accumulator.floor();
+            self.accumulator -= steps;
+            steps as u32
+        }
+    }
+
+    /// Resets the tick count, seed and pause state for a new world, the speed is kept
+    pub fn reset(&mut self) {
+        *self = SimulationControl { speed_index: self.speed_index, ..Default::default() }
+    }
+}
+
+/// Pauses, steps a single tick or changes the speed through the [`Action`]s bound to it
+pub fn user_controls_simulation(
+    actions: ActionInput,
+    mut control: ResMut<SimulationControl>,
+) {
+    if actions.just_pressed(Action::PauseSimulation) {
+        control.paused = !control.paused;
+        info!("Simulation {}", if control.paused { "paused" } else { "resumed" });
+    }
+    if actions.just_pressed(Action::StepSimulation) {
+        control.request_step();
+    }
+    if actions.just_pressed(Action::SpeedUp) {
+        control.faster();
+        info!("Simulation speed set to {}x", control.speed());
+    }
+    if actions.just_pressed(Action::SlowDown) {
+        control.slower();
+        info!("Simulation speed set to {}x", control.speed());
+    }
+}
+
+pub fn reset_simulation_control(mut control: ResMut<SimulationControl>) {
+    control.reset();
+}
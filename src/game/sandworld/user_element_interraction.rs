@@ -1,92 +1,208 @@
-use bevy::{ecs::{query::With, resource::Resource, system::{Local, Res, ResMut, Single}}, input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput}, math::Vec2, render::camera::Camera, transform::components::GlobalTransform, window::{PrimaryWindow, Window}};
-use crate::game::sandworld::{Elem, ElemKind, ElemPos, GridCells, GridParams, SandColor, GRID_SIZE};
+use bevy::{ecs::{event::{Event, EventReader, EventWriter}, query::With, resource::Resource, system::{Local, Res, ResMut, Single}}, math::Vec2, render::camera::Camera, transform::components::GlobalTransform, window::{PrimaryWindow, Window}};
+use crate::{actions::{Action, ActionInput}, game::{gamepad_cursor::VirtualCursor, sandworld::{edit_history::{EditHistory, HistoryMode}, replay::ReplayState, simulation_control::SimulationControl, wall_layer::WallCells, Elem, ElemKind, ElemPos, GridCells, GridParams, GridSize, SandColor}, stats::StatEvent}};
 
 #[derive(Resource)]
 pub struct UserSelectedElements{
     pub kind: ElemKind,
-    pub radius: u32
+    pub radius: u32,
+    pub layer: PaintLayer,
 }
 impl UserSelectedElements{ 
     pub fn single(kind: ElemKind) -> Self { 
-        UserSelectedElements { kind , radius: 1 }
+        UserSelectedElements { kind , radius: 1, layer: PaintLayer::World }
     }
 }
 
+/// Which cells the brush paints into
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum PaintLayer {
+    /// The simulated [`GridCells`]
+    #[default]
+    World,
+    /// The decorative [`WallCells`] behind them, painting there is not recorded in the [`EditHistory`]
+    Wall,
+}
+impl PaintLayer {
+    pub fn toggled(&self) -> Self {
+        match self {
+            PaintLayer::World => PaintLayer::Wall,
+            PaintLayer::Wall => PaintLayer::World,
+        }
+    }
+}
+
+/// Everything the user does to the grid, produced by the input systems (or a replay) and applied by [`apply_user_edits`]
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub enum UserEdit {
+    SelectElement(ElemKind),
+    SetRadius(u32),
+    SetLayer(PaintLayer),
+    /// Paints the selected element on the line from `from` (exclusive) to `to`, or only on `to`
+    Paint { from: Option<ElemPos>, to: ElemPos },
+    EndStroke,
+    Undo,
+    Redo,
+    SetHistoryMode(HistoryMode),
+}
+
+/// Order in which [`Action::SelectNextElement`] and [`Action::SelectPreviousElement`] go through the elements
+const SELECTION_ORDER: [ElemKind; 6] = [
+    ElemKind::Empty,
+    ElemKind::Sand(SandColor::Yellow),
+    ElemKind::Sand(SandColor::Red),
+    ElemKind::Sand(SandColor::Blue),
+    ElemKind::Sand(SandColor::Green),
+    ElemKind::Stone,
+];
+
 pub fn user_selects_element(
-    keys: Res<ButtonInput<KeyCode>>,
-    mut element_selection: ResMut<UserSelectedElements>,
+    actions: ActionInput,
+    element_selection: Res<UserSelectedElements>,
+    mut edits: EventWriter<UserEdit>,
 ) {
-    let toggled_elem_kind = if keys.just_pressed(KeyCode::KeyM) {
-        match element_selection.kind {
-            ElemKind::Empty => Some(ElemKind::Sand(SandColor::Yellow)),
-            ElemKind::Sand(SandColor::Yellow) => Some(ElemKind::Sand(SandColor::Red)),
-            ElemKind::Sand(SandColor::Red) => Some(ElemKind::Sand(SandColor::Blue)),
-            ElemKind::Sand(SandColor::Blue) => Some(ElemKind::Sand(SandColor::Green)),
-            ElemKind::Sand(SandColor::Green) => Some(ElemKind::Stone),
-            ElemKind::Stone => Some(ElemKind::Empty),
-        }
+    let step = if actions.just_pressed(Action::SelectNextElement) {
+        Some(1)
+    } else if actions.just_pressed(Action::SelectPreviousElement) {
+        Some(SELECTION_ORDER.len() - 1)
     } else { None };
 
-    let toggled_radius = if keys.just_pressed(KeyCode::KeyN) {
-        match element_selection.radius {
-            _ => Some(1),
-        }
+    let toggled_elem_kind = step.map(|step| {
+        let current = SELECTION_ORDER.iter().position(|kind| *kind == element_selection.kind).unwrap_or(0);
+        SELECTION_ORDER[(current + step) % SELECTION_ORDER.len()]
+    });
+
+    let toggled_radius = if actions.just_pressed(Action::ResetBrushRadius) {
+        Some(1)
     } else { None };
 
+    if actions.just_pressed(Action::ToggleWallLayer) {
+        edits.write(UserEdit::SetLayer(element_selection.layer.toggled()));
+    }
+
     if let Some(kind) = toggled_elem_kind {
-        element_selection.kind = kind
+        edits.write(UserEdit::SelectElement(kind));
     }
     if let Some(radius) = toggled_radius {
-        element_selection.radius = radius
+        edits.write(UserEdit::SetRadius(radius));
     }
 }
 
+#[derive(Default)]
 pub struct PrevMousePos(pub Option<ElemPos>);
-impl Default for PrevMousePos {
-    fn default() -> Self { PrevMousePos(None) }
-}
 
-/// If mouse button is pressed - calculates the coordinates of the cell over which the cursor is hovering
+/// If [`Action::PaintPrimary`] is held - calculates the coordinates of the cell over which the cursor is hovering,
+/// the [`VirtualCursor`] of a gamepad taking the place of the mouse while it is in use
 /// 
-/// Inserts the [`UserGeneratedElements`] resource with the selected square and the current [`UserSelectedElement`]
+/// Sends a [`UserEdit::Paint`] from the previously hovered cell, and a [`UserEdit::EndStroke`] once the button is released.
+/// [`Action::Erase`] paints empty cells, by selecting [`ElemKind::Empty`] for the stroke and restoring the selection after it
 pub fn user_adds_element(
     window: Single<&Window, With<PrimaryWindow>>,
     camera: Single<(&Camera, &GlobalTransform)>,
-    grid_q: Single<(&GlobalTransform, &GridParams, &mut GridCells)>,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
-    selected_elems: Res<UserSelectedElements>,
+    grid_q: Single<(&GlobalTransform, &GridParams, &GridCells)>,
+    actions: ActionInput,
+    virtual_cursor: Res<VirtualCursor>,
+    selection: Res<UserSelectedElements>,
+    mut edits: EventWriter<UserEdit>,
     mut previous_mouse_pos: Local<PrevMousePos>,
+    mut stroking: Local<bool>,
+    mut selection_before_erase: Local<Option<ElemKind>>,
+) {
+    let painting = actions.pressed(Action::PaintPrimary);
+    let erasing = !painting && actions.pressed(Action::Erase);
+
+    if erasing && selection_before_erase.is_none() {
+        *selection_before_erase = Some(selection.kind);
+        edits.write(UserEdit::SelectElement(ElemKind::Empty));
+    }
+
+    if painting || erasing {
+
+        if let Some(world_pos) = cursor_to_world(window, camera, &virtual_cursor) {
+            let (g_transform, grid_params, grid_cells) = grid_q.into_inner();
+            if let Some(current_pos) = world_to_grid(world_pos, g_transform, grid_params.scale, grid_cells.size()) {
+
+                edits.write(UserEdit::Paint { from: previous_mouse_pos.0, to: current_pos });
+                *stroking = true;
+
+                previous_mouse_pos.0 = Some(current_pos);
+                return 
+            }
+        } 
+    } else {
+        if *stroking {
+            edits.write(UserEdit::EndStroke);
+            *stroking = false;
+        }
+        if let Some(kind) = selection_before_erase.take() {
+            edits.write(UserEdit::SelectElement(kind));
+        }
+    }
+    previous_mouse_pos.0 = None
+}
+
+/// Applies the [`UserEdit`]s of this tick to the grid, recording strokes into the [`EditHistory`] and edits into the [`ReplayState`]
+pub fn apply_user_edits(
+    mut edits: EventReader<UserEdit>,
+    grid: Single<(&mut GridCells, &mut WallCells)>,
+    mut selected_elems: ResMut<UserSelectedElements>,
+    mut history: ResMut<EditHistory>,
+    mut replay: ResMut<ReplayState>,
+    control: Res<SimulationControl>,
+    mut stat_events: EventWriter<StatEvent>,
 ) {
-    if mouse_buttons.pressed(MouseButton::Left) 
-    || mouse_buttons.just_pressed(MouseButton::Left) {
+    let (mut grid_cells, mut wall_cells) = grid.into_inner();
+    let grid_cells = grid_cells.as_mut();
 
-        if let Some(world_pos) = cursor_to_world(window, camera) {
-            let (g_transform, grid_params, mut grid_cells) = grid_q.into_inner();
-            if let Some(current_pos) = world_to_grid(world_pos, g_transform, grid_params.scale) {
+    for edit in edits.read() {
+        replay.record(control.tick, *edit);
 
-                let all_click_squares = if let Some(previous_m_pos) = previous_mouse_pos.0 {
+        match *edit {
+            UserEdit::SelectElement(kind) => selected_elems.kind = kind,
+            UserEdit::SetRadius(radius) => selected_elems.radius = radius,
+            UserEdit::SetLayer(layer) => selected_elems.layer = layer,
+            UserEdit::Paint { from, to } => {
+                if !to.in_bounds(grid_cells.size()) || from.is_some_and(|from| !from.in_bounds(grid_cells.size())) { continue }
+
+                let all_click_squares = if let Some(previous_m_pos) = from {
                     bresenham_line(
                         previous_m_pos.x as i32,
                         previous_m_pos.y as i32, 
-                        current_pos.x as i32, 
-                        current_pos.y as i32, 
+                        to.x as i32, 
+                        to.y as i32, 
                     )
-                } else { vec![current_pos] };
+                } else { vec![to] };
 
+                if selected_elems.layer == PaintLayer::Wall {
+                    for sq_pos in all_click_squares {
+                        wall_cells.set_kind_at(sq_pos, selected_elems.kind);
+                    }
+                    continue
+                }
+
+                history.begin_stroke(grid_cells);
+
+                let mut painted = 0;
                 for sq_pos in all_click_squares {
+                    let Some(before) = grid_cells.get_elem_at(sq_pos) else { continue };
 
-                    if grid_cells.get_elem_at(sq_pos).unwrap().kind == ElemKind::Empty 
+                    if before.kind == ElemKind::Empty 
                     || selected_elems.kind == ElemKind::Empty {
-                        grid_cells.set_elem_at(sq_pos, Elem::new(selected_elems.kind, false,)).unwrap();
+                        let after = Elem::new(selected_elems.kind, false).seeded_at_tick(sq_pos, control.tick);
+                        grid_cells.set_elem_at(sq_pos, after).unwrap();
+                        history.record(sq_pos, before, after);
+                        painted += 1;
                     }
                 }
-
-                previous_mouse_pos.0 = Some(current_pos);
-                return 
+                if painted > 0 && !replay.is_playing() {
+                    stat_events.write(StatEvent::CellsPainted { kind: selected_elems.kind, count: painted });
+                }
             }
-        } 
-    } 
-    previous_mouse_pos.0 = None
+            UserEdit::EndStroke => history.end_stroke(),
+            UserEdit::Undo => { history.undo(grid_cells); },
+            UserEdit::Redo => { history.redo(grid_cells); },
+            UserEdit::SetHistoryMode(mode) => history.mode = mode,
+        }
+    }
 }
 
 fn bresenham_line(x0: i32, y0: i32, x1: i32, y1: i32) -> Vec<ElemPos> {
@@ -130,11 +246,12 @@ fn bresenham_line(x0: i32, y0: i32, x1: i32, y1: i32) -> Vec<ElemPos> {
 /// window cursor position to world cursor position
 fn cursor_to_world(
     window: Single<&Window, With<PrimaryWindow>>,
-    camera: Single<(&Camera, &GlobalTransform)>
+    camera: Single<(&Camera, &GlobalTransform)>,
+    virtual_cursor: &VirtualCursor,
 ) -> Option<Vec2> {
     let (camera, cam_transform) = camera.into_inner();
 
-    if let Some(screen_pos) = window.cursor_position() {
+    if let Some(screen_pos) = virtual_cursor.position_in(&window) {
         camera.viewport_to_world_2d(cam_transform, screen_pos)
             .ok()
     } else {
@@ -147,10 +264,11 @@ fn world_to_grid(
     world_pos: Vec2,
     sprite_transform: &GlobalTransform,
     scale: f32,
+    grid_size: GridSize,
 ) -> Option<ElemPos>{
     let sprite_center = sprite_transform.translation().truncate();
 
-    let size = Vec2::new(GRID_SIZE.width as f32 * scale , GRID_SIZE.height as f32 * scale );
+    let size = Vec2::new(grid_size.width as f32 * scale , grid_size.height as f32 * scale );
 
     let min = sprite_center - size / 2.0;
 
@@ -161,9 +279,9 @@ fn world_to_grid(
 
     if gx >= 0 
     && gy >= 0 
-    && gx < GRID_SIZE.width as isize 
-    && gy < GRID_SIZE.height as isize {
-        Some(ElemPos::new(gx as u32, GRID_SIZE.height - 1 - gy as u32 ))
+    && gx < grid_size.width as isize 
+    && gy < grid_size.height as isize {
+        Some(ElemPos::new(gx as u32, grid_size.height - 1 - gy as u32 ))
     } else {
         None
     }
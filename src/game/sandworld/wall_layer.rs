@@ -0,0 +1,55 @@
+use bevy::{color::{Color, Luminance}, ecs::component::Component};
+use crate::{game::sandworld::{Elem, ElemKind, ElemPos, GridCells, GridSize}, settings::ColorPalette};
+
+/// How much darker wall cells are drawn than the element they are painted with, so they read as background
+const WALL_DARKENING: f32 = 0.3;
+
+/// Decorative cells drawn behind the simulated ones wherever those are empty. They are never simulated,
+/// and are painted while [`PaintLayer::Wall`](super::user_element_interraction::PaintLayer::Wall) is selected
+#[derive(Component, Clone)]
+pub struct WallCells {
+    size: GridSize,
+    /// Row-major like [`GridCells::cells`](super::GridCells::cells), [`ElemKind::Empty`] where there is no wall
+    kinds: Vec<ElemKind>,
+}
+impl Default for WallCells {
+    fn default() -> Self {
+        WallCells::new_sized(GridSize::default())
+    }
+}
+impl WallCells {
+    /// A bare wall for a grid of `size`
+    pub fn new_sized(size: GridSize) -> Self {
+        WallCells { size, kinds: vec![ElemKind::Empty; size.count()] }
+    }
+
+    pub fn get_kind_at(&self, pos: ElemPos) -> Option<ElemKind> {
+        pos.in_bounds(self.size).then(|| self.kinds[self.size.index(pos)])
+    }
+
+    pub fn set_kind_at(&mut self, pos: ElemPos, kind: ElemKind) -> Option<()> {
+        if !pos.in_bounds(self.size) { return None }
+        let index = self.size.index(pos);
+        self.kinds[index] = kind;
+        Some(())
+    }
+
+    /// The wall as a grid of elements, so it can be stored in the world file format
+    pub fn to_grid_cells(&self) -> GridCells {
+        let mut grid_cells = GridCells::new_sized(self.size);
+        for (cell, kind) in grid_cells.cells.iter_mut().zip(&self.kinds) {
+            *cell = Elem::new(*kind, false);
+        }
+        grid_cells
+    }
+
+    pub fn from_grid_cells(grid_cells: &GridCells) -> Self {
+        WallCells { size: grid_cells.size(), kinds: grid_cells.cells.iter().map(|elem| elem.kind).collect() }
+    }
+
+    /// Darkened colour of the wall at `pos`, `None` where there is no wall
+    pub fn color_at(&self, pos: ElemPos, palette: ColorPalette) -> Option<Color> {
+        let kind = self.get_kind_at(pos).filter(|kind| *kind != ElemKind::Empty)?;
+        Some(Elem::new(kind, false).seeded_at(pos).color(pos, 0, palette).darker(WALL_DARKENING))
+    }
+}
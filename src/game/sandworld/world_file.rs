@@ -0,0 +1,309 @@
+use std::{fmt::Display, fs, io, path::Path};
+use crate::game::sandworld::{wall_layer::WallCells, Elem, ElemKind, ElemPos, GridCells, GridSize, SandColor, GRID_SIZE};
+
+/// Every world file starts with these bytes
+const MAGIC: [u8; 4] = *b"SNDW";
+/// Version written by [`encode_world`], older versions are migrated on load
+pub const WORLD_FILE_VERSION: u16 = 3;
+pub const WORLD_FILE_EXTENSION: &str = "sandw";
+
+const HEADER_LEN: usize = 4 + 2 + 4 + 4 + 8 + 8 + 4;
+const MOVED_BIT: u8 = 0b1000_0000;
+/// Flag after the seeds telling whether wall runs follow
+const NO_WALL: u8 = 0;
+const WITH_WALL: u8 = 1;
+/// Guards against files claiming absurd sizes before any cells are decoded
+const MAX_CELLS: usize = 4096 * 4096;
+
+/// Everything stored in a world file besides the cells themselves
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldMeta {
+    pub width: u32,
+    pub height: u32,
+    pub seed: u64,
+    pub tick: u64,
+}
+
+pub struct WorldData {
+    pub meta: WorldMeta,
+    /// Row-major, `meta.width * meta.height` long, with their colour seeds
+    pub cells: Vec<Elem>,
+    /// The [`WallCells`] saved with the world as elements laid out like `cells`, `None` if the file holds no wall
+    pub wall: Option<Vec<Elem>>,
+}
+impl WorldData {
+    /// Copies the cells into a grid of `size`, cropping or padding with empty cells from the top-left
+    pub fn to_grid_cells(&self, size: GridSize) -> GridCells {
+        self.fit(&self.cells, size)
+    }
+
+    /// The wall cropped or padded like [`WorldData::to_grid_cells`], bare if the file holds no wall
+    pub fn wall_cells(&self, size: GridSize) -> WallCells {
+        match &self.wall {
+            Some(wall) => WallCells::from_grid_cells(&self.fit(wall, size)),
+            None => WallCells::new_sized(size),
+        }
+    }
+
+    fn fit(&self, cells: &[Elem], size: GridSize) -> GridCells {
+        let mut grid_cells = GridCells::new_sized(size);
+
+        for y in 0..self.meta.height.min(size.height) {
+            for x in 0..self.meta.width.min(size.width) {
+                let elem = cells[y as usize * self.meta.width as usize + x as usize];
+                grid_cells.set_elem_at(ElemPos::new(x, y), elem);
+            }
+        }
+        grid_cells
+    }
+
+    pub fn fits_grid(&self, size: GridSize) -> bool {
+        self.meta.width == size.width && self.meta.height == size.height
+    }
+}
+
+#[derive(Debug)]
+pub enum WorldFileError {
+    Io(io::Error),
+    NotAWorldFile,
+    UnsupportedVersion(u16),
+    Truncated,
+    InvalidCell(u8),
+    TooLarge { width: u32, height: u32 },
+    CellCountMismatch { expected: usize, found: usize },
+    InvalidWallFlag(u8),
+}
+impl Display for WorldFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldFileError::Io(err) => write!(f, "could not access the world file: {err}"),
+            WorldFileError::NotAWorldFile => write!(f, "the file is not a world file"),
+            WorldFileError::UnsupportedVersion(version) => write!(f, "world file version {version} is newer than the supported version {WORLD_FILE_VERSION}"),
+            WorldFileError::Truncated => write!(f, "the world file ends unexpectedly"),
+            WorldFileError::InvalidCell(byte) => write!(f, "the world file contains an unknown cell {byte:#04x}"),
+            WorldFileError::TooLarge { width, height } => write!(f, "the world file is too large ({width}x{height})"),
+            WorldFileError::CellCountMismatch { expected, found } => write!(f, "the world file should hold {expected} cells but holds {found}"),
+            WorldFileError::InvalidWallFlag(flag) => write!(f, "the world file has an unknown wall flag {flag:#04x}"),
+        }
+    }
+}
+impl std::error::Error for WorldFileError {}
+impl From<io::Error> for WorldFileError {
+    fn from(err: io::Error) -> Self {
+        WorldFileError::Io(err)
+    }
+}
+
+/// Serializes the grid as a header followed by run-length encoded cells and the colour seeds of the particles
+///
+/// Layout (little endian): magic, version `u16`, width `u32`, height `u32`, seed `u64`, tick `u64`,
+/// run count `u32`, then `run count` pairs of run length `u16` and cell byte, then the seed `u16` of every
+/// non-empty cell row by row, then a wall flag `u8`. The flag is 0 here, see [`encode_world_with_wall`]
+pub fn encode_world(grid_cells: &GridCells, seed: u64, tick: u64) -> Vec<u8> {
+    let mut bytes = encode_cells(grid_cells, seed, tick);
+    bytes.push(NO_WALL);
+    bytes
+}
+
+/// Like [`encode_world`] with the wall flag set to 1, followed by the wall as its own run count `u32` and runs
+pub fn encode_world_with_wall(grid_cells: &GridCells, wall_cells: &WallCells, seed: u64, tick: u64) -> Vec<u8> {
+    let mut bytes = encode_cells(grid_cells, seed, tick);
+    bytes.push(WITH_WALL);
+    push_runs(&mut bytes, &encode_runs(&wall_cells.to_grid_cells().cells));
+    bytes
+}
+
+/// Everything up to the wall flag
+fn encode_cells(grid_cells: &GridCells, seed: u64, tick: u64) -> Vec<u8> {
+    let runs = encode_runs(&grid_cells.cells);
+    let particles: Vec<&Elem> = grid_cells.cells.iter().filter(|elem| elem.kind != ElemKind::Empty).collect();
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + runs.len() * 3 + particles.len() * 2 + 1);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&WORLD_FILE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&grid_cells.size().width.to_le_bytes());
+    bytes.extend_from_slice(&grid_cells.size().height.to_le_bytes());
+    bytes.extend_from_slice(&seed.to_le_bytes());
+    bytes.extend_from_slice(&tick.to_le_bytes());
+    push_runs(&mut bytes, &runs);
+    for elem in particles {
+        bytes.extend_from_slice(&elem.seed.to_le_bytes());
+    }
+    bytes
+}
+
+fn push_runs(bytes: &mut Vec<u8>, runs: &[(u16, u8)]) {
+    bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+    for (len, cell) in runs {
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.push(*cell);
+    }
+}
+
+pub fn decode_world(bytes: &[u8]) -> Result<WorldData, WorldFileError> {
+    let mut reader = ByteReader::new(bytes);
+
+    if reader.take(4).map_err(|_| WorldFileError::NotAWorldFile)? != MAGIC {
+        return Err(WorldFileError::NotAWorldFile)
+    }
+
+    match reader.u16()? {
+        1 => decode_v1(&mut reader),
+        2 => decode_v2(&mut reader),
+        3 => decode_v3(&mut reader),
+        version => Err(WorldFileError::UnsupportedVersion(version)),
+    }
+}
+
+pub fn save_world(path: &Path, grid_cells: &GridCells, wall_cells: &WallCells, seed: u64, tick: u64) -> Result<(), WorldFileError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, encode_world_with_wall(grid_cells, wall_cells, seed, tick))?;
+    Ok(())
+}
+
+pub fn load_world(path: &Path) -> Result<WorldData, WorldFileError> {
+    decode_world(&fs::read(path)?)
+}
+
+/// Version 1 stored no colour seeds, every particle is seeded by its position as it was when loaded back then
+fn decode_v1(reader: &mut ByteReader) -> Result<WorldData, WorldFileError> {
+    let mut world = decode_cells(reader)?;
+    let width = world.meta.width;
+    for (i, elem) in world.cells.iter_mut().enumerate() {
+        *elem = elem.seeded_at(ElemPos::new(i as u32 % width, i as u32 / width));
+    }
+    Ok(world)
+}
+
+/// Version 2 added the colour seeds but stored no wall
+fn decode_v2(reader: &mut ByteReader) -> Result<WorldData, WorldFileError> {
+    let mut world = decode_cells(reader)?;
+    for elem in world.cells.iter_mut().filter(|elem| elem.kind != ElemKind::Empty) {
+        elem.seed = reader.u16()?;
+    }
+    Ok(world)
+}
+
+/// Version 3 is the current layout, decoders for later versions should return data migrated to the newest [`WorldData`]
+fn decode_v3(reader: &mut ByteReader) -> Result<WorldData, WorldFileError> {
+    let mut world = decode_v2(reader)?;
+    world.wall = match reader.u8()? {
+        NO_WALL => None,
+        WITH_WALL => Some(decode_runs(reader, world.cells.len())?),
+        flag => return Err(WorldFileError::InvalidWallFlag(flag)),
+    };
+    Ok(world)
+}
+
+/// The header and the run-length encoded cells shared by every version, the cells are not seeded yet
+fn decode_cells(reader: &mut ByteReader) -> Result<WorldData, WorldFileError> {
+    let meta = WorldMeta {
+        width: reader.u32()?,
+        height: reader.u32()?,
+        seed: reader.u64()?,
+        tick: reader.u64()?,
+    };
+    let expected = meta.width as usize * meta.height as usize;
+    if expected > MAX_CELLS {
+        return Err(WorldFileError::TooLarge { width: meta.width, height: meta.height })
+    }
+    let cells = decode_runs(reader, expected)?;
+    Ok(WorldData { meta, cells, wall: None })
+}
+
+/// A run count followed by runs adding up to exactly `expected` cells
+fn decode_runs(reader: &mut ByteReader, expected: usize) -> Result<Vec<Elem>, WorldFileError> {
+    let run_count = reader.u32()?;
+
+    let mut cells = Vec::with_capacity(expected.min(GRID_SIZE.count()));
+    for _ in 0..run_count {
+        let len = reader.u16()? as usize;
+        let elem = byte_to_elem(reader.u8()?)?;
+        if cells.len() + len > expected {
+            return Err(WorldFileError::CellCountMismatch { expected, found: cells.len() + len })
+        }
+        cells.extend(std::iter::repeat_n(elem, len));
+    }
+
+    if cells.len() != expected {
+        return Err(WorldFileError::CellCountMismatch { expected, found: cells.len() })
+    }
+    Ok(cells)
+}
+
+fn encode_runs(cells: &[Elem]) -> Vec<(u16, u8)> {
+    let mut runs: Vec<(u16, u8)> = Vec::new();
+
+    for elem in cells {
+        let cell = elem_to_byte(*elem);
+        match runs.last_mut() {
+            Some((len, last)) if *last == cell && *len < u16::MAX => *len += 1,
+            _ => runs.push((1, cell)),
+        }
+    }
+    runs
+}
+
+/// Stable on-disk code of an element kind, shared by every file format
+pub fn kind_to_byte(kind: ElemKind) -> u8 {
+    match kind {
+        ElemKind::Empty => 0,
+        ElemKind::Stone => 1,
+        ElemKind::Sand(SandColor::Yellow) => 2,
+        ElemKind::Sand(SandColor::Red) => 3,
+        ElemKind::Sand(SandColor::Blue) => 4,
+        ElemKind::Sand(SandColor::Green) => 5,
+    }
+}
+
+pub fn byte_to_kind(byte: u8) -> Option<ElemKind> {
+    match byte {
+        0 => Some(ElemKind::Empty),
+        1 => Some(ElemKind::Stone),
+        2 => Some(ElemKind::Sand(SandColor::Yellow)),
+        3 => Some(ElemKind::Sand(SandColor::Red)),
+        4 => Some(ElemKind::Sand(SandColor::Blue)),
+        5 => Some(ElemKind::Sand(SandColor::Green)),
+        _ => None,
+    }
+}
+
+fn elem_to_byte(elem: Elem) -> u8 {
+    let kind = kind_to_byte(elem.kind);
+    if elem.moved { kind | MOVED_BIT } else { kind }
+}
+
+fn byte_to_elem(byte: u8) -> Result<Elem, WorldFileError> {
+    let kind = byte_to_kind(byte & !MOVED_BIT).ok_or(WorldFileError::InvalidCell(byte))?;
+    Ok(Elem::new(kind, byte & MOVED_BIT != 0))
+}
+
+/// Little endian reader over a byte slice, running out of bytes is reported as [`WorldFileError::Truncated`]
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes, pos: 0 }
+    }
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], WorldFileError> {
+        let slice = self.bytes.get(self.pos..self.pos + len).ok_or(WorldFileError::Truncated)?;
+        self.pos += len;
+        Ok(slice)
+    }
+    pub fn u8(&mut self) -> Result<u8, WorldFileError> {
+        Ok(self.take(1)?[0])
+    }
+    pub fn u16(&mut self) -> Result<u16, WorldFileError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    pub fn u32(&mut self) -> Result<u32, WorldFileError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    pub fn u64(&mut self) -> Result<u64, WorldFileError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
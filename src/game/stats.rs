@@ -0,0 +1,114 @@
+use std::{collections::BTreeMap, fs, io, path::{Path, PathBuf}};
+use bevy::{app::AppExit, ecs::{event::{Event, EventReader}, resource::Resource, system::{Res, ResMut}}, log::{error, warn}, time::Time};
+use serde::{Deserialize, Serialize};
+use crate::{game::sandworld::ElemKind, settings::APP_DIR};
+
+const STATS_FILE: &str = "stats.toml";
+pub const STATS_VERSION: u32 = 1;
+
+/// Sent by the game systems whenever something tracked in the [`Stats`] happens
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub enum StatEvent {
+    /// Cells changed by the user painting, replays are not counted
+    CellsPainted { kind: ElemKind, count: u64 },
+    TicksSimulated(u64),
+}
+
+/// Gameplay metrics accumulated over every session, kept in [`stats_path`]
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Stats {
+    pub version: u32,
+    /// Time spent in game while it was not paused
+    pub play_time_secs: f64,
+    pub ticks_simulated: u64,
+    /// Painted cell counts by [`ElemKind::name`]
+    pub cells_painted: BTreeMap<String, u64>,
+}
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            version: STATS_VERSION,
+            play_time_secs: 0.,
+            ticks_simulated: 0,
+            cells_painted: BTreeMap::new(),
+        }
+    }
+}
+impl Stats {
+    pub fn cells_painted_of(&self, kind: ElemKind) -> u64 {
+        self.cells_painted.get(kind.name()).copied().unwrap_or(0)
+    }
+
+    /// Loads the stats file, starting from zero if it is missing or unreadable
+    pub fn load_or_default() -> Self {
+        let path = stats_path();
+        match fs::read_to_string(&path) {
+            Ok(text) => match toml::from_str::<Stats>(&text) {
+                Ok(stats) if stats.version <= STATS_VERSION => Stats { version: STATS_VERSION, ..stats },
+                Ok(stats) => {
+                    warn!("Stats version {} is newer than the supported version {STATS_VERSION}, starting from zero", stats.version);
+                    Stats::default()
+                }
+                Err(err) => {
+                    warn!("The stats file {} is invalid, starting from zero: {err}", path.display());
+                    Stats::default()
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Stats::default(),
+            Err(err) => {
+                warn!("Could not read {}, starting from zero: {err}", path.display());
+                Stats::default()
+            }
+        }
+    }
+}
+
+/// `stats.toml` in the platform local data directory, or in the working directory if there is none
+pub fn stats_path() -> PathBuf {
+    dirs::data_local_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .unwrap_or_default()
+        .join(STATS_FILE)
+}
+
+pub fn save_stats(path: &Path, stats: &Stats) -> io::Result<()> {
+    let text = toml::to_string_pretty(stats).map_err(io::Error::other)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)
+}
+
+pub fn record_stat_events(
+    mut events: EventReader<StatEvent>,
+    mut stats: ResMut<Stats>,
+) {
+    for event in events.read() {
+        match *event {
+            StatEvent::CellsPainted { kind, count } => *stats.cells_painted.entry(kind.name().into()).or_default() += count,
+            StatEvent::TicksSimulated(ticks) => stats.ticks_simulated += ticks,
+        }
+    }
+}
+
+pub fn track_play_time(time: Res<Time>, mut stats: ResMut<Stats>) {
+    stats.play_time_secs += time.delta_secs_f64();
+}
+
+fn write_stats(stats: &Stats) {
+    if let Err(err) = save_stats(&stats_path(), stats) {
+        error!("Could not save stats: {err}");
+    }
+}
+
+/// Stats change every frame in game, so they are only written when leaving the game or closing the app
+pub fn save_stats_on_leave(stats: Res<Stats>) {
+    write_stats(&stats);
+}
+
+pub fn save_stats_on_exit(exit: EventReader<AppExit>, stats: Res<Stats>) {
+    if !exit.is_empty() {
+        write_stats(&stats);
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::{Path, PathBuf};
+use bevy::{color::palettes::css::YELLOW, ecs::{component::Component, event::{Event, EventReader, EventWriter}, query::{Changed, With}, system::{Commands, Query, Res, ResMut, Single}}, input::{keyboard::KeyCode, ButtonInput}, log::{error, info, warn}, window::FileDragAndDrop, prelude::{children, SpawnRelated}, text::{TextColor, TextFont}, ui::{widget::{Button, Text}, AlignItems, BackgroundColor, FlexDirection, Interaction, JustifyContent, Node, PositionType, UiRect, Val}, utils::default};
+use crate::{game::sandworld::{edit_history::EditHistory, simulation_control::SimulationControl, png_import::{load_png_world, ImportFit}, replay::{PlayReplay, ReplayState, REPLAY_FILE_EXTENSION}, user_element_interraction::UserSelectedElements, wall_layer::WallCells, world_file::{load_world, save_world, WORLD_FILE_EXTENSION}, GridCells}, menu::menu::{NORMAL_BUTTON, TEXT_COLOR}};
+
+const SAVE_DIR: &str = "saves";
+
+/// Asks for the current world to be written to the given path
+#[derive(Event)]
+pub struct SaveWorld(pub PathBuf);
+
+/// Asks for the current world to be replaced by the one stored at the given path
+#[derive(Event)]
+pub struct LoadWorld(pub PathBuf);
+
+#[derive(Component)]
+pub struct WorldMenu;
+
+#[derive(Component)]
+pub struct WorldMenuStatus;
+
+#[derive(Component)]
+pub enum WorldMenuButtonAction {
+    Save,
+    Load,
+}
+
+pub fn quicksave_path() -> PathBuf {
+    Path::new(SAVE_DIR).join(format!("quicksave.{WORLD_FILE_EXTENSION}"))
+}
+
+/// Small panel in the top-left corner with the Save and Load buttons and the result of the last file operation
+pub fn setup_world_menu(
+    mut commands: Commands,
+) {
+    let button_node = Node {
+        width: Val::Px(220.0),
+        height: Val::Px(48.75),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_font = TextFont {
+        font_size: 33.0,
+        ..default()
+    };
+
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            top: Val::Px(20.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BackgroundColor(YELLOW.into()),
+        WorldMenu,
+        children![
+            (
+                Button,
+                button_node.clone(),
+                BackgroundColor(NORMAL_BUTTON),
+                WorldMenuButtonAction::Save,
+                children![
+                    (
+                        Text::new("Save"),
+                        button_text_font.clone(),
+                        TextColor(TEXT_COLOR),
+                    ),
+                ]
+            ),
+            (
+                Button,
+                button_node,
+                BackgroundColor(NORMAL_BUTTON),
+                WorldMenuButtonAction::Load,
+                children![
+                    (
+                        Text::new("Load"),
+                        button_text_font.clone(),
+                        TextColor(TEXT_COLOR),
+                    ),
+                ]
+            ),
+            (
+                Text::new(""),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(TEXT_COLOR),
+                Node {
+                    max_width: Val::Px(240.0),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                WorldMenuStatus,
+            ),
+        ],
+    ));
+}
+
+pub fn world_menu_action(
+    interaction_query: Query<
+        (&Interaction, &WorldMenuButtonAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut save_events: EventWriter<SaveWorld>,
+    mut load_events: EventWriter<LoadWorld>,
+) {
+    for (interaction, action) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match action {
+                WorldMenuButtonAction::Save => { save_events.write(SaveWorld(quicksave_path())); },
+                WorldMenuButtonAction::Load => { load_events.write(LoadWorld(quicksave_path())); },
+            }
+        }
+    }
+}
+
+/// Ctrl+S saves and Ctrl+O loads the quicksave
+pub fn world_file_shortcuts(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut save_events: EventWriter<SaveWorld>,
+    mut load_events: EventWriter<LoadWorld>,
+) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) { return }
+
+    if keys.just_pressed(KeyCode::KeyS) {
+        save_events.write(SaveWorld(quicksave_path()));
+    }
+    if keys.just_pressed(KeyCode::KeyO) {
+        load_events.write(LoadWorld(quicksave_path()));
+    }
+}
+
+pub fn save_world_on_event(
+    mut events: EventReader<SaveWorld>,
+    grid: Single<(&GridCells, &WallCells)>,
+    control: Res<SimulationControl>,
+    mut status: Query<&mut Text, With<WorldMenuStatus>>,
+) {
+    let (grid_cells, wall_cells) = *grid;
+    for SaveWorld(path) in events.read() {
+        let message = match save_world(path, grid_cells, wall_cells, control.seed, control.tick) {
+            Ok(()) => {
+                info!("Saved world to {}", path.display());
+                format!("Saved to {}", path.display())
+            }
+            Err(err) => {
+                error!("Could not save world to {}: {err}", path.display());
+                format!("Save failed: {err}")
+            }
+        };
+        set_status(&mut status, message);
+    }
+}
+
+/// Replaces the grid, wall, tick count and seed with the loaded world, the edit history is cleared and replays restarted.
+/// Worlds saved without a wall load with a bare one
+pub fn load_world_on_event(
+    mut events: EventReader<LoadWorld>,
+    grid: Single<(&mut GridCells, &mut WallCells)>,
+    mut control: ResMut<SimulationControl>,
+    mut history: ResMut<EditHistory>,
+    mut replay: ResMut<ReplayState>,
+    selection: Res<UserSelectedElements>,
+    mut status: Query<&mut Text, With<WorldMenuStatus>>,
+) {
+    let (mut grid_cells, mut wall_cells) = grid.into_inner();
+    for LoadWorld(path) in events.read() {
+        let message = match load_world(path) {
+            Ok(world) => {
+                let size = grid_cells.size();
+                if !world.fits_grid(size) {
+                    warn!("World in {} is {}x{}, it was cropped to the current grid", path.display(), world.meta.width, world.meta.height);
+                }
+                *grid_cells = world.to_grid_cells(size);
+                *wall_cells = world.wall_cells(size);
+                control.tick = world.meta.tick;
+                control.seed = world.meta.seed;
+                history.clear();
+                replay.world_replaced(&grid_cells, &wall_cells, &mut control, &selection, &mut history);
+
+                info!("Loaded world from {}", path.display());
+                format!("Loaded {}", path.display())
+            }
+            Err(err) => {
+                error!("Could not load world from {}: {err}", path.display());
+                format!("Load failed: {err}")
+            }
+        };
+        set_status(&mut status, message);
+    }
+}
+
+/// Dropping a PNG onto the window imports it as a new world, resized to the grid or cropped while Shift is held.
+/// Dropping a world file loads it and dropping a replay file plays it back
+pub fn import_dropped_files(
+    mut drops: EventReader<FileDragAndDrop>,
+    keys: Res<ButtonInput<KeyCode>>,
+    grid: Single<(&mut GridCells, &mut WallCells)>,
+    mut control: ResMut<SimulationControl>,
+    mut history: ResMut<EditHistory>,
+    mut replay: ResMut<ReplayState>,
+    selection: Res<UserSelectedElements>,
+    mut load_events: EventWriter<LoadWorld>,
+    mut replay_events: EventWriter<PlayReplay>,
+    mut status: Query<&mut Text, With<WorldMenuStatus>>,
+) {
+    let (mut grid_cells, mut wall_cells) = grid.into_inner();
+    for drop in drops.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = drop else { continue };
+        let extension = path_buf.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+
+        match extension.as_deref() {
+            Some("png") => {
+                let fit = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) { ImportFit::Crop } else { ImportFit::Resize };
+
+                let message = match load_png_world(path_buf, fit, grid_cells.size()) {
+                    Ok(imported) => {
+                        *grid_cells = imported;
+                        *wall_cells = WallCells::new_sized(grid_cells.size());
+                        control.tick = 0;
+                        history.clear();
+                        replay.world_replaced(&grid_cells, &wall_cells, &mut control, &selection, &mut history);
+
+                        info!("Imported {} ({fit:?})", path_buf.display());
+                        format!("Imported {}", path_buf.display())
+                    }
+                    Err(err) => {
+                        error!("Could not import {}: {err}", path_buf.display());
+                        format!("Import failed: {err}")
+                    }
+                };
+                set_status(&mut status, message);
+            }
+            Some(ext) if ext == WORLD_FILE_EXTENSION => {
+                load_events.write(LoadWorld(path_buf.clone()));
+            }
+            Some(ext) if ext == REPLAY_FILE_EXTENSION => {
+                replay_events.write(PlayReplay(path_buf.clone()));
+            }
+            _ => {
+                warn!("Ignoring dropped file {}", path_buf.display());
+            }
+        }
+    }
+}
+
+fn set_status(status: &mut Query<&mut Text, With<WorldMenuStatus>>, message: String) {
+    for mut text in status.iter_mut() {
+        text.0 = message.clone();
+    }
+}
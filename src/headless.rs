@@ -0,0 +1,143 @@
+use std::{fmt::Display, fs, io, path::{Path, PathBuf}};
+use bevy::{app::{App, Update}, MinimalPlugins};
+use crate::game::{sandworld::{draw_image::GridStyle, main_interaction::main_interaction_loop, png_export::{grid_to_rgba, scale_image, ExportScale}, png_import::{load_png_world, ImportFit, PngImportError}, simulation_control::SimulationControl, wall_layer::WallCells, world_file::{load_world, WorldFileError}, GridCells, GridSize}, stats::StatEvent};
+
+const DEFAULT_TICKS: u64 = 600;
+const USAGE: &str = "usage: sandfall-mimimi --headless <world.sandw|map.png> [--ticks N] [--out-png PATH] [--out-json PATH] [--upscale]";
+
+/// Options of a headless run, parsed from the arguments following `--headless`
+pub struct HeadlessArgs {
+    pub input: PathBuf,
+    pub ticks: u64,
+    pub out_png: PathBuf,
+    pub out_json: PathBuf,
+    pub scale: ExportScale,
+}
+impl HeadlessArgs {
+    /// Returns `None` if `--headless` is not among the arguments
+    pub fn parse(args: &[String]) -> Option<Result<Self, HeadlessError>> {
+        let start = args.iter().position(|arg| arg == "--headless")?;
+        Some(Self::parse_after_flag(&args[start + 1..]))
+    }
+
+    fn parse_after_flag(args: &[String]) -> Result<Self, HeadlessError> {
+        let mut input = None;
+        let mut ticks = DEFAULT_TICKS;
+        let mut out_png = PathBuf::from("headless-out.png");
+        let mut out_json = PathBuf::from("headless-out.json");
+        let mut scale = ExportScale::Native;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| HeadlessError::Usage(format!("missing value for {arg}")));
+            match arg.as_str() {
+                "--ticks" => {
+                    let raw = value()?;
+                    ticks = raw.parse().map_err(|_| HeadlessError::Usage(format!("invalid tick count {raw}")))?;
+                }
+                "--out-png" => out_png = PathBuf::from(value()?),
+                "--out-json" => out_json = PathBuf::from(value()?),
+                "--upscale" => scale = ExportScale::Upscaled,
+                flag if flag.starts_with("--") => return Err(HeadlessError::Usage(format!("unknown option {flag}"))),
+                path => input = Some(PathBuf::from(path)),
+            }
+        }
+
+        Ok(HeadlessArgs {
+            input: input.ok_or_else(|| HeadlessError::Usage("missing input file".into()))?,
+            ticks,
+            out_png,
+            out_json,
+            scale,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum HeadlessError {
+    Usage(String),
+    World(WorldFileError),
+    Png(PngImportError),
+    Export(image::ImageError),
+    Io(io::Error),
+}
+impl Display for HeadlessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeadlessError::Usage(message) => write!(f, "{message}\n{USAGE}"),
+            HeadlessError::World(err) => write!(f, "{err}"),
+            HeadlessError::Png(err) => write!(f, "{err}"),
+            HeadlessError::Export(err) => write!(f, "could not write the result image: {err}"),
+            HeadlessError::Io(err) => write!(f, "could not write the result counts: {err}"),
+        }
+    }
+}
+impl std::error::Error for HeadlessError {}
+
+/// Loads a world file or a PNG as the starting grid, together with its seed and tick
+pub fn load_start_grid(input: &Path) -> Result<(GridCells, SimulationControl), HeadlessError> {
+    let mut control = SimulationControl::default();
+    let is_png = input.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+
+    let grid_cells = if is_png {
+        load_png_world(input, ImportFit::Resize, GridSize::default()).map_err(HeadlessError::Png)?
+    } else {
+        let world = load_world(input).map_err(HeadlessError::World)?;
+        control.tick = world.meta.tick;
+        control.seed = world.meta.seed;
+        world.to_grid_cells(GridSize::default())
+    };
+    Ok((grid_cells, control))
+}
+
+/// Runs [`main_interaction_loop`] in an [`App`] with only the [`MinimalPlugins`] until `ticks` more steps have run
+pub fn simulate(grid_cells: GridCells, mut control: SimulationControl, ticks: u64) -> (GridCells, SimulationControl) {
+    let target = control.tick + ticks;
+    control.run_until = Some(target);
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(control)
+        .add_event::<StatEvent>()
+        .add_systems(Update, main_interaction_loop);
+    let grid_e = app.world_mut().spawn(grid_cells).id();
+
+    while app.world().resource::<SimulationControl>().tick < target {
+        app.update();
+    }
+
+    let grid_cells = app.world_mut().entity_mut(grid_e).take::<GridCells>().unwrap();
+    let control = app.world_mut().remove_resource::<SimulationControl>().unwrap();
+    (grid_cells, control)
+}
+
+/// Per-kind cell counts as a small JSON document
+pub fn counts_json(grid_cells: &GridCells, tick: u64) -> String {
+    let counts = grid_cells
+        .count_kinds()
+        .iter()
+        .map(|(kind, count)| format!("    \"{}\": {count}", kind.name()))
+        .collect::<Vec<String>>()
+        .join(",\n");
+
+    format!("{{\n  \"tick\": {tick},\n  \"counts\": {{\n{counts}\n  }}\n}}\n")
+}
+
+pub fn run_headless(args: HeadlessArgs) -> Result<(), HeadlessError> {
+    let (grid_cells, control) = load_start_grid(&args.input)?;
+    let (grid_cells, control) = simulate(grid_cells, control, args.ticks);
+
+    scale_image(&grid_to_rgba(&grid_cells, &WallCells::default(), GridStyle::default(), control.tick), args.scale)
+        .save(&args.out_png)
+        .map_err(HeadlessError::Export)?;
+    fs::write(&args.out_json, counts_json(&grid_cells, control.tick)).map_err(HeadlessError::Io)?;
+
+    println!(
+        "Simulated {} ticks of {}, wrote {} and {}",
+        args.ticks,
+        args.input.display(),
+        args.out_png.display(),
+        args.out_json.display(),
+    );
+    Ok(())
+}
@@ -0,0 +1,25 @@
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+use bevy::state::state::{StateSet, States, SubStates};
+
+pub mod actions;
+pub mod game;
+pub mod headless;
+pub mod menu;
+pub mod settings;
+pub mod utils;
+
+#[derive(States, Debug, Hash, Eq, PartialEq, Clone)]
+pub enum AppState {
+    MainMenu,
+    InGame,
+}
+
+/// Whether the world is running or frozen behind the pause menu, only exists while in game
+#[derive(SubStates, Default, Debug, Hash, Eq, PartialEq, Clone)]
+#[source(AppState = AppState::InGame)]
+pub enum GameState {
+    #[default]
+    Running,
+    Paused,
+}
@@ -1,11 +1,16 @@
-use bevy::{app::{App, PluginGroup}, log::LogPlugin, render::texture::ImagePlugin, state::{app::AppExtStates, state::States}, window::{Window, WindowPlugin, WindowResolution}, DefaultPlugins};
-use crate::{game::game::GamePlugin, menu::menu::MenuPlugin};
-
-mod game;
-mod menu;
-mod utils;
+use bevy::{app::{App, PluginGroup}, log::LogPlugin, render::texture::ImagePlugin, state::app::AppExtStates, window::{Window, WindowPlugin, WindowResizeConstraints, WindowResolution}, DefaultPlugins};
+use sandfall_mimimi::{actions::ActionsPlugin, game::{game::GamePlugin, sandworld::GRID_SIZE}, headless::{run_headless, HeadlessArgs}, menu::menu::MenuPlugin, settings::SettingsPlugin, AppState};
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(headless_args) = HeadlessArgs::parse(&args) {
+        if let Err(err) = headless_args.and_then(run_headless) {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+        return
+    }
+
     let mut app = App::new();
 
     app.add_plugins((
@@ -16,6 +21,11 @@ fn main() {
                         Some(Window{
                             title: "Sandrissi".into(),
                             resolution: WindowResolution::new(960., 540. ),
+                            resize_constraints: WindowResizeConstraints {
+                                min_width: GRID_SIZE.width as f32,
+                                min_height: GRID_SIZE.height as f32,
+                                ..Default::default()
+                            },
                             ..Default::default()
                         }), 
                         ..Default::default()
@@ -27,6 +37,8 @@ fn main() {
                 level: bevy::log::Level::DEBUG,
                 custom_layer: |_| None,
             }),
+        SettingsPlugin,
+        ActionsPlugin,
         MenuPlugin,
         GamePlugin,
     ))
@@ -35,10 +47,4 @@ fn main() {
     .insert_state(AppState::MainMenu);
 
     app.run();
-}
-
-#[derive(States, Debug, Hash, Eq, PartialEq, Clone)]
-pub enum AppState {
-    MainMenu,
-    InGame,
 }
\ No newline at end of file
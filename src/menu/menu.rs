@@ -1,8 +1,8 @@
-use bevy::{app::{AppExit, Plugin, Update}, color::{palettes::css::YELLOW, Color}, ecs::{ component::Component, entity::Entity, event::EventWriter, query::{Changed, With}, schedule::IntoScheduleConfigs, system::{Commands, Query, ResMut}}, prelude::{children, SpawnRelated}, state::{app::AppExtStates, condition::in_state, state::{NextState, OnEnter, OnExit}}, text::{TextColor, TextFont}, ui::{widget::{Button, Text}, AlignItems, BackgroundColor, FlexDirection, Interaction, JustifyContent, Node, UiRect, Val}, utils::default};
-use crate::{menu::MenuState, AppState};
+use bevy::{app::{AppExit, Plugin, PreUpdate, Update}, color::{palettes::css::YELLOW, Color}, ecs::{ bundle::Bundle, change_detection::{DetectChanges, Ref}, component::Component, entity::Entity, event::EventWriter, hierarchy::ChildSpawnerCommands, query::{Changed, With}, resource::Resource, schedule::{Condition, IntoScheduleConfigs}, system::{Commands, Query, Res, ResMut}}, input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput}, prelude::{children, SpawnRelated}, state::{app::AppExtStates, condition::in_state, state::{NextState, OnEnter, OnExit}}, text::{TextColor, TextFont}, ui::{widget::{Button, Text}, AlignItems, BackgroundColor, Display, FlexDirection, FlexWrap, Interaction, JustifyContent, Node, UiRect, UiSystem, Val}, utils::default};
+use crate::{actions::{Action, ActionMap, Binding}, game::{game::NewWorld, sandworld::{recording::{FRAME_SKIP_STEPS, MAX_FRAMES_STEPS}, simulation_control::SPEED_STEPS, ElemKind, GridParams, GridSize}, stats::Stats}, menu::{navigation::{gamepad_goes_back, navigate_menu, MenuFocus}, MenuState}, settings::{BackgroundMode, ColorPalette, DisplayMode, LightingMode, Settings}, AppState, GameState};
 
-const TEXT_COLOR: Color = Color::srgb(0., 0., 0.);
-const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
+pub const TEXT_COLOR: Color = Color::srgb(0., 0., 0.);
+pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
 const HOVERED_PRESSED_BUTTON: Color = Color::srgb(0.25, 0.65, 0.25);
 const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
@@ -12,6 +12,8 @@ impl Plugin for MenuPlugin {
     fn build(&self, app: &mut bevy::app::App) {
 
         app.init_state::<MenuState>();
+        app.init_resource::<RebindState>();
+        app.init_resource::<MenuFocus>();
         
         app.add_systems(
             OnEnter(AppState::MainMenu), 
@@ -25,30 +27,181 @@ impl Plugin for MenuPlugin {
             OnExit(MenuState::Main), 
             despawn_screen::<MainMenuScreen>,
         )
+        .add_systems(
+            OnEnter(MenuState::Settings), 
+            setup_settings_menu
+        )
+        .add_systems(
+            OnExit(MenuState::Settings), 
+            (despawn_screen::<SettingsMenuScreen>, stop_rebinding),
+        )
+        .add_systems(
+            OnEnter(MenuState::Stats), 
+            setup_stats_menu
+        )
+        .add_systems(
+            OnExit(MenuState::Stats), 
+            despawn_screen::<StatsMenuScreen>,
+        )
         .add_systems(
             OnEnter(MenuState::Quit), 
             exit_game
         )
         .add_systems(
             Update, 
-            (menu_action, button_system)
+            (
+                setting_button::<DisplayModeOption>,
+                setting_button::<SpeedOption>,
+                setting_button::<GridSizeOption>,
+                setting_button::<PaletteOption>,
+                setting_button::<PatternOption>,
+                setting_button::<LightingOption>,
+                setting_button::<BackgroundOption>,
+                setting_button::<FrameSkipOption>,
+                setting_button::<MaxFramesOption>,
+                (binding_button, capture_binding, refresh_binding_labels).chain(),
+            )
+        )
+        .add_systems(
+            Update, 
+            menu_action
                 .run_if(in_state(AppState::MainMenu))
-        );
+        )
+        // Before the menus read the button interactions, so a key or gamepad press is handled the same frame as a click
+        .add_systems(
+            PreUpdate,
+            (navigate_menu, gamepad_goes_back)
+                .after(UiSystem::Focus)
+                .run_if(in_state(AppState::MainMenu).or(in_state(GameState::Paused)))
+        )
+        .add_systems(Update, button_system);
     }
 }
 
 #[derive(Component)]
 pub struct MainMenuScreen;
 
+#[derive(Component)]
+pub struct SettingsMenuScreen;
+
 #[derive(Component)]
 pub struct StatsMenuScreen;
 
 #[derive(Component)]
 pub enum MenuButtonAction {
+    Continue,
     Play,
+    Settings,
+    Stats,
+    BackToMainMenu,
     Quit,
 }
 
+/// The [`Action`] waiting for a new binding in a settings screen, if any
+#[derive(Resource, Default)]
+pub struct RebindState {
+    action: Option<Action>,
+    /// The click on the binding button is not taken as the new binding
+    waiting_release: bool,
+}
+impl RebindState {
+    pub fn is_rebinding(&self) -> bool {
+        self.action.is_some()
+    }
+}
+
+#[derive(Component)]
+struct BindingButton(Action);
+
+#[derive(Component)]
+struct BindingLabel(Action);
+
+#[derive(Component)]
+pub struct BindingStatus;
+
+/// A button choosing one value of a [`Settings`] field
+trait SettingOption: Component + Copy {
+    fn label(&self) -> String;
+    fn is_current(&self, settings: &Settings) -> bool;
+    fn apply(&self, settings: &mut Settings);
+}
+
+#[derive(Component, Clone, Copy)]
+struct DisplayModeOption(DisplayMode);
+impl SettingOption for DisplayModeOption {
+    fn label(&self) -> String { format!("{:?}", self.0) }
+    fn is_current(&self, settings: &Settings) -> bool { settings.display_mode == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.display_mode = self.0 }
+}
+
+#[derive(Component, Clone, Copy)]
+struct SpeedOption(f32);
+impl SettingOption for SpeedOption {
+    fn label(&self) -> String { format!("{}x", self.0) }
+    fn is_current(&self, settings: &Settings) -> bool { settings.sim_speed == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.sim_speed = self.0 }
+}
+
+/// Applies to the next New Game, a running world keeps its size
+#[derive(Component, Clone, Copy)]
+struct GridSizeOption(GridSize);
+impl SettingOption for GridSizeOption {
+    fn label(&self) -> String { format!("{}x{}", self.0.width, self.0.height) }
+    fn is_current(&self, settings: &Settings) -> bool { settings.grid_size() == self.0 }
+    fn apply(&self, settings: &mut Settings) {
+        settings.grid_width = self.0.width;
+        settings.grid_height = self.0.height;
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+struct PaletteOption(ColorPalette);
+impl SettingOption for PaletteOption {
+    fn label(&self) -> String { self.0.label().into() }
+    fn is_current(&self, settings: &Settings) -> bool { settings.palette == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.palette = self.0 }
+}
+
+#[derive(Component, Clone, Copy)]
+struct LightingOption(LightingMode);
+impl SettingOption for LightingOption {
+    fn label(&self) -> String { format!("{:?}", self.0) }
+    fn is_current(&self, settings: &Settings) -> bool { settings.lighting == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.lighting = self.0 }
+}
+
+#[derive(Component, Clone, Copy)]
+struct BackgroundOption(BackgroundMode);
+impl SettingOption for BackgroundOption {
+    fn label(&self) -> String { format!("{:?}", self.0) }
+    fn is_current(&self, settings: &Settings) -> bool { settings.background == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.background = self.0 }
+}
+
+#[derive(Component, Clone, Copy)]
+struct PatternOption(bool);
+impl SettingOption for PatternOption {
+    fn label(&self) -> String { if self.0 { "On" } else { "Off" }.into() }
+    fn is_current(&self, settings: &Settings) -> bool { settings.sand_patterns == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.sand_patterns = self.0 }
+}
+
+#[derive(Component, Clone, Copy)]
+struct FrameSkipOption(u32);
+impl SettingOption for FrameSkipOption {
+    fn label(&self) -> String { if self.0 == 0 { "All".into() } else { format!("1 in {}", self.0 + 1) } }
+    fn is_current(&self, settings: &Settings) -> bool { settings.recording_frame_skip == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.recording_frame_skip = self.0 }
+}
+
+#[derive(Component, Clone, Copy)]
+struct MaxFramesOption(usize);
+impl SettingOption for MaxFramesOption {
+    fn label(&self) -> String { self.0.to_string() }
+    fn is_current(&self, settings: &Settings) -> bool { settings.recording_max_frames == self.0 }
+    fn apply(&self, settings: &mut Settings) { settings.recording_max_frames = self.0 }
+}
+
 // Tag component used to mark which setting is currently selected
 #[derive(Component)]
 struct SelectedOption;
@@ -59,8 +212,10 @@ fn menu_setup(
     menu_state.set(MenuState::Main);
 }
 
+/// The Continue button is only shown while a world from an earlier game is still alive
 fn setup_main_menu(
     mut commands: Commands,
+    world: Query<(), With<GridParams>>,
 ) {
     let button_node = Node {
             width: Val::Px(300.0),
@@ -106,6 +261,22 @@ fn setup_main_menu(
                     },
 
                 ),
+                (
+                    Button,
+                    Node {
+                        display: if world.is_empty() { Display::None } else { Display::Flex },
+                        ..button_node.clone()
+                    },
+                    BackgroundColor(NORMAL_BUTTON),
+                    MenuButtonAction::Continue,
+                    children![
+                        (
+                            Text::new("Continue"),
+                            button_text_font.clone(),
+                            TextColor(TEXT_COLOR),
+                        ),
+                    ]
+                ),
                 (
                     Button,
                     button_node.clone(),
@@ -119,6 +290,32 @@ fn setup_main_menu(
                         ),
                     ]
                 ),
+                (
+                    Button,
+                    button_node.clone(),
+                    BackgroundColor(NORMAL_BUTTON),
+                    MenuButtonAction::Settings,
+                    children![
+                        (
+                            Text::new("Settings"),
+                            button_text_font.clone(),
+                            TextColor(TEXT_COLOR),
+                        ),
+                    ]
+                ),
+                (
+                    Button,
+                    button_node.clone(),
+                    BackgroundColor(NORMAL_BUTTON),
+                    MenuButtonAction::Stats,
+                    children![
+                        (
+                            Text::new("Stats"),
+                            button_text_font.clone(),
+                            TextColor(TEXT_COLOR),
+                        ),
+                    ]
+                ),
                 (
                     Button,
                     button_node,
@@ -137,21 +334,393 @@ fn setup_main_menu(
     ));
 }
 
+fn setup_settings_menu(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    action_map: Res<ActionMap>,
+) {
+    spawn_settings_screen(&mut commands, &settings, &action_map, SettingsMenuScreen, MenuButtonAction::BackToMainMenu);
+}
+
+/// One row per setting with a button for every value, the current one marked with [`SelectedOption`]
+///
+/// Shared by the main menu and the pause menu, `screen` marks the root for [`despawn_screen`] and `back` is the action of the Back button
+pub fn spawn_settings_screen(
+    commands: &mut Commands,
+    settings: &Settings,
+    action_map: &ActionMap,
+    screen: impl Bundle,
+    back: impl Component,
+) {
+    let button_node = Node {
+            width: Val::Px(300.0),
+            height: Val::Px(48.75),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+    };
+    let button_text_font = TextFont {
+        font_size: 33.0,
+        ..default()
+    };
+
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        screen,
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            Node{
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(YELLOW.into()),
+        ))
+        .with_children(|panel| {
+            spawn_option_row(panel, "Display", DisplayMode::ALL.map(DisplayModeOption), settings);
+            spawn_option_row(panel, "Speed", SPEED_STEPS.map(SpeedOption), settings);
+            spawn_option_row(panel, "Grid size", GridSize::ALL.map(GridSizeOption), settings);
+            spawn_option_row(panel, "Palette", ColorPalette::ALL.map(PaletteOption), settings);
+            spawn_option_row(panel, "Patterns", [false, true].map(PatternOption), settings);
+            spawn_option_row(panel, "Lighting", LightingMode::ALL.map(LightingOption), settings);
+            spawn_option_row(panel, "Background", BackgroundMode::ALL.map(BackgroundOption), settings);
+            spawn_option_row(panel, "Rec. frames", FRAME_SKIP_STEPS.map(FrameSkipOption), settings);
+            spawn_option_row(panel, "Rec. length", MAX_FRAMES_STEPS.map(MaxFramesOption), settings);
+            spawn_bindings(panel, action_map);
+
+            panel.spawn((
+                Button,
+                button_node,
+                BackgroundColor(NORMAL_BUTTON),
+                back,
+                children![
+                    (
+                        Text::new("Back"),
+                        button_text_font,
+                        TextColor(TEXT_COLOR),
+                    ),
+                ]
+            ));
+        });
+    });
+}
+
+fn spawn_option_row<T: SettingOption>(
+    panel: &mut ChildSpawnerCommands,
+    label: &str,
+    options: impl IntoIterator<Item = T>,
+    settings: &Settings,
+) {
+    let option_text_font = TextFont {
+        font_size: 25.0,
+        ..default()
+    };
+
+    panel.spawn(Node {
+        align_items: AlignItems::Center,
+        margin: UiRect::horizontal(Val::Px(20.0)),
+        ..default()
+    })
+    .with_children(|row| {
+        row.spawn((
+            Text::new(label),
+            option_text_font.clone(),
+            TextColor(TEXT_COLOR),
+            Node {
+                width: Val::Px(150.0),
+                ..default()
+            },
+        ));
+        for option in options {
+            let selected = option.is_current(settings);
+            let mut button = row.spawn((
+                Button,
+                Node {
+                    width: Val::Px(120.0),
+                    height: Val::Px(40.0),
+                    margin: UiRect::all(Val::Px(8.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                BackgroundColor(if selected { PRESSED_BUTTON } else { NORMAL_BUTTON }),
+                option,
+                children![
+                    (
+                        Text::new(option.label()),
+                        option_text_font.clone(),
+                        TextColor(TEXT_COLOR),
+                    ),
+                ]
+            ));
+            if selected {
+                button.insert(SelectedOption);
+            }
+        }
+    });
+}
+
+/// The binding of every [`Action`] in two columns, each with a button to rebind it, and a line reporting the last rebinding
+fn spawn_bindings(panel: &mut ChildSpawnerCommands, action_map: &ActionMap) {
+    let binding_text_font = TextFont {
+        font_size: 22.0,
+        ..default()
+    };
+
+    panel.spawn(Node {
+        flex_wrap: FlexWrap::Wrap,
+        width: Val::Px(880.0),
+        margin: UiRect::top(Val::Px(10.0)),
+        ..default()
+    })
+    .with_children(|grid| {
+        for action in Action::ALL {
+            grid.spawn(Node {
+                width: Val::Px(440.0),
+                align_items: AlignItems::Center,
+                ..default()
+            })
+            .with_children(|entry| {
+                entry.spawn((
+                    Text::new(action.label()),
+                    binding_text_font.clone(),
+                    TextColor(TEXT_COLOR),
+                    Node {
+                        width: Val::Px(220.0),
+                        margin: UiRect::left(Val::Px(20.0)),
+                        ..default()
+                    },
+                ));
+                entry.spawn((
+                    Button,
+                    Node {
+                        width: Val::Px(180.0),
+                        height: Val::Px(34.0),
+                        margin: UiRect::all(Val::Px(4.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    BackgroundColor(NORMAL_BUTTON),
+                    BindingButton(action),
+                    children![
+                        (
+                            Text::new(action_map.get(action).to_string()),
+                            binding_text_font.clone(),
+                            TextColor(TEXT_COLOR),
+                            BindingLabel(action),
+                        ),
+                    ]
+                ));
+            });
+        }
+    });
+
+    panel.spawn((
+        Text::new("Click a binding, then press the new key or mouse button"),
+        binding_text_font,
+        TextColor(TEXT_COLOR),
+        BindingStatus,
+    ));
+}
+
+/// Moves the [`SelectedOption`] of a setting to the pressed button and stores its value
+fn setting_button<T: SettingOption>(
+    interaction_query: Query<(&Interaction, &T, Entity), (Changed<Interaction>, With<Button>)>,
+    mut selected_query: Query<(Entity, &mut BackgroundColor), (With<SelectedOption>, With<T>)>,
+    mut commands: Commands,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, option, entity) in &interaction_query {
+        if *interaction == Interaction::Pressed && !option.is_current(&settings) {
+            for (previous, mut background_color) in &mut selected_query {
+                *background_color = NORMAL_BUTTON.into();
+                commands.entity(previous).remove::<SelectedOption>();
+            }
+            commands.entity(entity).insert(SelectedOption);
+            option.apply(&mut settings);
+        }
+    }
+}
+
+/// Lists the persistent [`Stats`], one line per metric
+fn setup_stats_menu(
+    mut commands: Commands,
+    stats: Res<Stats>,
+) {
+    let button_node = Node {
+            width: Val::Px(300.0),
+            height: Val::Px(48.75),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+    };
+    let button_text_font = TextFont {
+        font_size: 33.0,
+        ..default()
+    };
+    let stat_text_font = TextFont {
+        font_size: 25.0,
+        ..default()
+    };
+
+    let secs = stats.play_time_secs as u64;
+    let mut lines = vec![
+        format!("Play time: {}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60),
+        format!("Ticks simulated: {}", stats.ticks_simulated),
+        "Cells painted:".to_string(),
+    ];
+    lines.extend(ElemKind::ALL.iter().map(|kind| format!("{kind} {}", stats.cells_painted_of(*kind))));
+
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        StatsMenuScreen,
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            Node{
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(20.0)),
+                ..default()
+            },
+            BackgroundColor(YELLOW.into()),
+        ))
+        .with_children(|panel| {
+            for line in lines {
+                panel.spawn((
+                    Text::new(line),
+                    stat_text_font.clone(),
+                    TextColor(TEXT_COLOR),
+                    Node {
+                        margin: UiRect::vertical(Val::Px(4.0)),
+                        ..default()
+                    },
+                ));
+            }
+
+            panel.spawn((
+                Button,
+                button_node,
+                BackgroundColor(NORMAL_BUTTON),
+                MenuButtonAction::BackToMainMenu,
+                children![
+                    (
+                        Text::new("Back"),
+                        button_text_font,
+                        TextColor(TEXT_COLOR),
+                    ),
+                ]
+            ));
+        });
+    });
+}
+
+/// Starts waiting for the new binding of the pressed [`BindingButton`]
+fn binding_button(
+    interaction_query: Query<(&Interaction, &BindingButton), (Changed<Interaction>, With<Button>)>,
+    mut rebind: ResMut<RebindState>,
+    mut status: Query<&mut Text, With<BindingStatus>>,
+) {
+    for (interaction, BindingButton(action)) in &interaction_query {
+        if *interaction == Interaction::Pressed && rebind.action.is_none() {
+            *rebind = RebindState { action: Some(*action), waiting_release: true };
+            for mut text in &mut status {
+                text.0 = format!("Press the new binding for {}", action.label());
+            }
+        }
+    }
+}
+
+/// Binds the first key or mouse button pressed after the click that started the rebinding.
+/// A binding used by another action is swapped with it, a reserved key keeps waiting for another one
+fn capture_binding(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut rebind: ResMut<RebindState>,
+    mut action_map: ResMut<ActionMap>,
+    mut status: Query<&mut Text, With<BindingStatus>>,
+) {
+    let Some(action) = rebind.action else { return };
+    if rebind.waiting_release {
+        rebind.waiting_release = mouse.get_pressed().next().is_some();
+        return
+    }
+    let Some(binding) = Binding::just_pressed(&keys, &mouse) else { return };
+
+    let rebound = action_map.rebind_swapping(action, binding);
+    let message = match rebound {
+        Ok(Some(other)) => format!("{} bound to {binding}, {} took its old binding {}", action.label(), other.label(), action_map.get(other)),
+        Ok(None) => format!("{} bound to {binding}", action.label()),
+        Err(err) => format!("{err}, press another binding for {}", action.label()),
+    };
+    for mut text in &mut status {
+        text.0 = message.clone();
+    }
+    if rebound.is_ok() {
+        rebind.action = None;
+    }
+}
+
+fn refresh_binding_labels(
+    rebind: Res<RebindState>,
+    action_map: Res<ActionMap>,
+    mut labels: Query<(&BindingLabel, &mut Text)>,
+) {
+    if !rebind.is_changed() && !action_map.is_changed() { return }
+
+    for (BindingLabel(action), mut text) in &mut labels {
+        text.0 = if rebind.action == Some(*action) { "...".into() } else { action_map.get(*action).to_string() };
+    }
+}
+
+/// Leaving a settings screen cancels a rebinding that is still waiting for input
+pub fn stop_rebinding(mut rebind: ResMut<RebindState>) {
+    *rebind = RebindState::default();
+}
+
+pub fn not_rebinding(rebind: Res<RebindState>) -> bool {
+    !rebind.is_rebinding()
+}
+
 fn exit_game(
     mut app_exit_events: EventWriter<AppExit>,
 ) {
     app_exit_events.write(AppExit::Success);
 }
 
-// This system handles changing all buttons color based on mouse interaction
+// This system handles changing all buttons color based on mouse interaction,
+// the button focused with the keyboard or a gamepad looks hovered
 fn button_system(
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, Option<&SelectedOption>),
-        (Changed<Interaction>, With<Button>),
+        (Entity, Ref<Interaction>, &mut BackgroundColor, Option<&SelectedOption>),
+        With<Button>,
     >,
+    focus: Res<MenuFocus>,
 ) {
-    for (interaction, mut background_color, selected) in &mut interaction_query {
-        *background_color = match (*interaction, selected) {
+    for (entity, interaction, mut background_color, selected) in &mut interaction_query {
+        if !interaction.is_changed() && !focus.is_changed() { continue }
+
+        let interaction = match *interaction {
+            Interaction::None if focus.is_focused(entity) => Interaction::Hovered,
+            interaction => interaction,
+        };
+        *background_color = match (interaction, selected) {
             (Interaction::Pressed, _) | (Interaction::None, Some(_)) => PRESSED_BUTTON.into(),
             (Interaction::Hovered, Some(_)) => HOVERED_PRESSED_BUTTON.into(),
             (Interaction::Hovered, None) => HOVERED_BUTTON.into(),
@@ -166,16 +735,25 @@ fn menu_action(
         (&Interaction, &MenuButtonAction),
         (Changed<Interaction>, With<Button>),
     >,
+    mut commands: Commands,
     mut menu_state: ResMut<NextState<MenuState>>,
     mut app_state: ResMut<NextState<AppState>>,
 ) {
     for (interaction, menu_button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match menu_button_action {
+                MenuButtonAction::Continue => {
+                    app_state.set(AppState::InGame);
+                    menu_state.set(MenuState::Disabled);
+                }
                 MenuButtonAction::Play => {
+                    commands.insert_resource(NewWorld);
                     app_state.set(AppState::InGame);
                     menu_state.set(MenuState::Disabled);
                 }
+                MenuButtonAction::Settings => menu_state.set(MenuState::Settings),
+                MenuButtonAction::Stats => menu_state.set(MenuState::Stats),
+                MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main),
                 MenuButtonAction::Quit => menu_state.set(MenuState::Quit),
             }
         }
@@ -184,7 +762,7 @@ fn menu_action(
 
 
 // Generic system that takes a component as a parameter, and will despawn all entities with that component
-fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
+pub fn despawn_screen<T: Component>(to_despawn: Query<Entity, With<T>>, mut commands: Commands) {
     for entity in &to_despawn {
         commands.entity(entity).despawn();
     }
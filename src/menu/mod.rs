@@ -1,11 +1,15 @@
 use bevy::state::state::States;
 
+#[allow(clippy::module_inception)]
 pub mod menu;
+pub mod navigation;
 
 #[derive(States, Default, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum MenuState{
     #[default]
     Disabled,
     Main,
+    Settings,
+    Stats,
     Quit
 }
\ No newline at end of file
@@ -0,0 +1,189 @@
+use bevy::{ecs::{entity::Entity, query::{With, Without}, resource::Resource, system::{Local, Query, Res, ResMut}}, input::{gamepad::{Gamepad, GamepadButton}, keyboard::KeyCode, ButtonInput}, math::Vec2, render::view::InheritedVisibility, state::state::{NextState, State}, transform::components::GlobalTransform, ui::{widget::{Button, Text}, ComputedNode, Interaction}};
+use crate::{game::{pause_menu::PauseMenuState, world_menu::WorldMenuButtonAction}, menu::{menu::{BindingStatus, RebindState}, MenuState}, GameState};
+
+/// How far the left stick has to be tilted to move the focus, it has to come back under it before moving again
+const STICK_THRESHOLD: f32 = 0.5;
+/// Buttons off to the side of the direction count this much more than buttons straight ahead
+const SIDEWAYS_PENALTY: f32 = 2.;
+const CONFIRM_KEYS: [KeyCode; 2] = [KeyCode::Enter, KeyCode::NumpadEnter];
+
+/// The menu button selected with the keyboard or a gamepad, drawn like a hovered button
+///
+/// Nothing is focused until the keyboard or a gamepad is used in a menu. After that the focus moves to the
+/// first button of every new screen, so it never has to be picked up again
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+    pub focused: Option<Entity>,
+    /// The button held down with Enter or the South button, released along with it
+    pressed: Option<Entity>,
+}
+impl MenuFocus {
+    pub fn is_focused(&self, entity: Entity) -> bool {
+        self.focused == Some(entity)
+    }
+}
+
+/// How the focus is asked to move
+#[derive(Clone, Copy)]
+enum FocusMove {
+    /// Towards the nearest button in a direction of UI space
+    Direction(Vec2),
+    /// To the next or previous button in reading order, wrapping around
+    Next,
+    Previous,
+}
+
+/// The arrow keys, the D-pad or the left stick move the [`MenuFocus`] to the nearest button in that direction, and
+/// Tab and Shift+Tab to the next and previous button. Enter or the South button press the focused button, by setting
+/// its [`Interaction`] so every menu reacts to it like to a click.
+///
+/// All visible buttons take part, except the world menu panel which stays reachable with the mouse only.
+/// Nothing moves while a settings screen waits for a new binding, so the keys reach the rebinding
+pub fn navigate_menu(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    rebind: Res<RebindState>,
+    buttons: Query<(Entity, &GlobalTransform, &ComputedNode, &InheritedVisibility), (With<Button>, Without<WorldMenuButtonAction>)>,
+    mut interactions: Query<&mut Interaction, With<Button>>,
+    mut focus: ResMut<MenuFocus>,
+    mut stick_tilted: Local<bool>,
+) {
+    let release = keys.any_just_released(CONFIRM_KEYS)
+        || gamepads.iter().any(|gamepad| gamepad.just_released(GamepadButton::South));
+    if release
+        && let Some(mut interaction) = focus.pressed.take().and_then(|entity| interactions.get_mut(entity).ok())
+        && *interaction == Interaction::Pressed
+    {
+        *interaction = Interaction::None;
+    }
+
+    let stick_direction = gamepad_stick_direction(&gamepads, &mut stick_tilted);
+    if rebind.is_rebinding() { return }
+
+    let focus_move = keyboard_focus_move(&keys)
+        .or_else(|| gamepad_dpad_direction(&gamepads).map(FocusMove::Direction))
+        .or(stick_direction.map(FocusMove::Direction));
+    let confirm = keys.any_just_pressed(CONFIRM_KEYS)
+        || gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::South));
+
+    // UI positions are in physical pixels with y growing downwards, hidden buttons have no size.
+    // Sorted in reading order
+    let mut visible: Vec<(Entity, Vec2)> = buttons
+        .iter()
+        .filter(|(_, _, node, visibility)| visibility.get() && !node.is_empty())
+        .map(|(entity, transform, ..)| (entity, transform.translation().truncate()))
+        .collect();
+    visible.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    let current = focus.focused.and_then(|focused| visible.iter().position(|(entity, _)| *entity == focused));
+
+    let Some(current) = current else {
+        // Focus the new screen after a focused button went away, or the first screen once a menu input is used
+        if (focus.focused.is_some() || focus_move.is_some() || confirm) && let Some((first, _)) = visible.first() {
+            focus.focused = Some(*first);
+        }
+        return
+    };
+
+    let next = focus_move.and_then(|focus_move| match focus_move {
+        FocusMove::Direction(direction) => nearest_in_direction(&visible, current, direction),
+        FocusMove::Next => Some((current + 1) % visible.len()),
+        FocusMove::Previous => Some((current + visible.len() - 1) % visible.len()),
+    });
+
+    if let Some(next) = next {
+        focus.focused = Some(visible[next].0);
+    } else if confirm && let Ok(mut interaction) = interactions.get_mut(visible[current].0) {
+        *interaction = Interaction::Pressed;
+        focus.pressed = Some(visible[current].0);
+    }
+}
+
+/// The East button goes back one screen, like [`Action::Pause`](crate::actions::Action::Pause) does in the pause menu.
+/// While a settings screen waits for a new binding it cancels the rebinding instead
+pub fn gamepad_goes_back(
+    gamepads: Query<&Gamepad>,
+    menu_state: Res<State<MenuState>>,
+    pause_menu_state: Option<Res<State<PauseMenuState>>>,
+    mut next_menu_state: ResMut<NextState<MenuState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut next_pause_menu_state: ResMut<NextState<PauseMenuState>>,
+    mut rebind: ResMut<RebindState>,
+    mut status: Query<&mut Text, With<BindingStatus>>,
+) {
+    if !gamepads.iter().any(|gamepad| gamepad.just_pressed(GamepadButton::East)) { return }
+
+    if rebind.is_rebinding() {
+        *rebind = RebindState::default();
+        for mut text in &mut status {
+            text.0 = "Rebinding cancelled".into();
+        }
+        return
+    }
+
+    match (menu_state.get(), pause_menu_state.as_deref().map(State::get)) {
+        (MenuState::Settings | MenuState::Stats, _) => next_menu_state.set(MenuState::Main),
+        (_, Some(PauseMenuState::Main)) => next_game_state.set(GameState::Running),
+        (_, Some(_)) => next_pause_menu_state.set(PauseMenuState::Main),
+        _ => {}
+    }
+}
+
+fn keyboard_focus_move(keys: &ButtonInput<KeyCode>) -> Option<FocusMove> {
+    const ARROWS: [(KeyCode, Vec2); 4] = [
+        (KeyCode::ArrowUp, Vec2::NEG_Y),
+        (KeyCode::ArrowDown, Vec2::Y),
+        (KeyCode::ArrowLeft, Vec2::NEG_X),
+        (KeyCode::ArrowRight, Vec2::X),
+    ];
+    if keys.just_pressed(KeyCode::Tab) {
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        return Some(if shift { FocusMove::Previous } else { FocusMove::Next })
+    }
+    ARROWS.into_iter()
+        .find(|(key, _)| keys.just_pressed(*key))
+        .map(|(_, direction)| FocusMove::Direction(direction))
+}
+
+fn gamepad_dpad_direction(gamepads: &Query<&Gamepad>) -> Option<Vec2> {
+    const DPAD: [(GamepadButton, Vec2); 4] = [
+        (GamepadButton::DPadUp, Vec2::NEG_Y),
+        (GamepadButton::DPadDown, Vec2::Y),
+        (GamepadButton::DPadLeft, Vec2::NEG_X),
+        (GamepadButton::DPadRight, Vec2::X),
+    ];
+    DPAD.into_iter()
+        .find(|(button, _)| gamepads.iter().any(|gamepad| gamepad.just_pressed(*button)))
+        .map(|(_, direction)| direction)
+}
+
+/// Direction of the left stick on the frame it is tilted past [`STICK_THRESHOLD`], in UI space
+fn gamepad_stick_direction(gamepads: &Query<&Gamepad>, stick_tilted: &mut bool) -> Option<Vec2> {
+    let stick = gamepads.iter().map(Gamepad::left_stick).find(|stick| stick.length() > STICK_THRESHOLD);
+    let was_tilted = *stick_tilted;
+    *stick_tilted = stick.is_some();
+
+    let stick = stick.filter(|_| !was_tilted)?;
+    Some(if stick.x.abs() > stick.y.abs() {
+        Vec2::new(stick.x.signum(), 0.)
+    } else {
+        // The stick points up, UI coordinates grow downwards
+        Vec2::new(0., -stick.y.signum())
+    })
+}
+
+/// Index of the closest button ahead in the direction, buttons straight ahead being preferred over ones to the side
+fn nearest_in_direction(buttons: &[(Entity, Vec2)], current: usize, direction: Vec2) -> Option<usize> {
+    let from = buttons[current].1;
+    buttons.iter()
+        .enumerate()
+        .filter(|(index, _)| *index != current)
+        .filter_map(|(index, (_, position))| {
+            let offset = *position - from;
+            let ahead = offset.dot(direction);
+            let sideways = offset.perp_dot(direction).abs();
+            (ahead > 0.).then_some((index, ahead + sideways * SIDEWAYS_PENALTY))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+}
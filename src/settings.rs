@@ -0,0 +1,333 @@
+use std::{collections::BTreeMap, fmt::Display, fs, io, path::{Path, PathBuf}, time::Duration};
+use bevy::{app::{AppExit, Last, Plugin, PostUpdate, Startup, Update}, ecs::{change_detection::DetectChanges, event::EventReader, query::With, resource::Resource, schedule::{common_conditions::resource_exists, IntoScheduleConfigs}, system::{Local, Res, ResMut, Single}}, log::{error, info, warn}, state::state::OnEnter, time::{Real, Time}, window::{MonitorSelection, PrimaryWindow, Window, WindowMode, WindowResized}};
+use serde::{Deserialize, Serialize};
+use crate::{game::{game::NewWorld, sandworld::{recording::{DEFAULT_FRAME_SKIP, DEFAULT_MAX_FRAMES}, simulation_control::SimulationControl, user_element_interraction::{PaintLayer, UserSelectedElements}, ElemKind, GridSize, GRID_SIZE}}, AppState};
+
+/// Version written by [`save_settings`], files without a version are read as version 1
+pub const SETTINGS_VERSION: u32 = 1;
+const SETTINGS_FILE: &str = "settings.toml";
+/// Name of the directory the game keeps its files in, inside the platform directories
+pub const APP_DIR: &str = "sandrisso";
+/// Settings are written once they stopped changing for this long, so resizing the window does not write them every frame
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    Fullscreen,
+}
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 2] = [DisplayMode::Windowed, DisplayMode::Fullscreen];
+
+    pub fn window_mode(&self) -> WindowMode {
+        match self {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Fullscreen => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+        }
+    }
+
+    pub fn from_window_mode(mode: WindowMode) -> Self {
+        match mode {
+            WindowMode::Windowed => DisplayMode::Windowed,
+            _ => DisplayMode::Fullscreen,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorPalette {
+    #[default]
+    Standard,
+    /// Red and green apart by brightness and hue, for green-weak vision
+    Deuteranopia,
+    /// Like [`ColorPalette::Deuteranopia`] with a brighter red, which red-weak vision sees darkened
+    Protanopia,
+    /// Yellow and blue apart by brightness, for blue-weak vision
+    Tritanopia,
+    /// Saturated colours far apart in brightness, without per-cell variation
+    HighContrast,
+}
+impl ColorPalette {
+    pub const ALL: [ColorPalette; 5] = [
+        ColorPalette::Standard,
+        ColorPalette::Deuteranopia,
+        ColorPalette::Protanopia,
+        ColorPalette::Tritanopia,
+        ColorPalette::HighContrast,
+    ];
+
+    /// Short name fitting a settings button
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorPalette::Standard => "Standard",
+            ColorPalette::Deuteranopia => "Deutan",
+            ColorPalette::Protanopia => "Protan",
+            ColorPalette::Tritanopia => "Tritan",
+            ColorPalette::HighContrast => "Contrast",
+        }
+    }
+}
+
+/// Light given off by hot elements, see [`LightMap`](crate::game::sandworld::lighting::LightMap)
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LightingMode {
+    #[default]
+    Off,
+    /// Cells are dimmed to the ambient light and brightened near emitters
+    Lit,
+    /// Like [`LightingMode::Lit`] with a halo around emitters
+    Glow,
+}
+impl LightingMode {
+    pub const ALL: [LightingMode; 3] = [LightingMode::Off, LightingMode::Lit, LightingMode::Glow];
+}
+
+/// What is drawn behind the transparent cells of the grid
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BackgroundMode {
+    #[default]
+    Solid,
+    Gradient,
+    /// The picture at [`Settings::background_image`]
+    Image,
+}
+impl BackgroundMode {
+    pub const ALL: [BackgroundMode; 3] = [BackgroundMode::Solid, BackgroundMode::Gradient, BackgroundMode::Image];
+}
+
+/// User preferences kept across restarts in [`settings_path`]
+///
+/// Missing fields take their default value, so files written by older versions still load
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u32,
+    pub display_mode: DisplayMode,
+    /// Logical size of the window when it is not fullscreen
+    pub window_width: u32,
+    pub window_height: u32,
+    /// [`ElemKind::name`] of the element selected when a game starts
+    pub brush_element: String,
+    pub brush_radius: u32,
+    pub sim_speed: f32,
+    /// Size of the grid a new world starts with, see [`Settings::grid_size`]
+    pub grid_width: u32,
+    pub grid_height: u32,
+    pub palette: ColorPalette,
+    /// Draws a texture over each sand colour, see [`SandColor::has_pattern_at`](crate::game::sandworld::SandColor::has_pattern_at)
+    pub sand_patterns: bool,
+    pub lighting: LightingMode,
+    pub background: BackgroundMode,
+    /// Path of the picture shown by [`BackgroundMode::Image`]
+    pub background_image: String,
+    /// Between 0 and 1
+    pub volume: f32,
+    /// Key names by action name, actions without an entry use their default binding
+    pub key_bindings: BTreeMap<String, String>,
+    /// Frames dropped between two captured ones of a GIF or PNG recording, see [`Recorder`](crate::game::sandworld::recording::Recorder)
+    pub recording_frame_skip: u32,
+    /// Frames a recording keeps, the oldest are dropped past that
+    pub recording_max_frames: usize,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            display_mode: DisplayMode::Windowed,
+            window_width: 960,
+            window_height: 540,
+            brush_element: ElemKind::Empty.name().into(),
+            brush_radius: 1,
+            sim_speed: 1.,
+            grid_width: GRID_SIZE.width,
+            grid_height: GRID_SIZE.height,
+            palette: ColorPalette::Standard,
+            sand_patterns: false,
+            lighting: LightingMode::Off,
+            background: BackgroundMode::Solid,
+            background_image: String::new(),
+            volume: 1.,
+            key_bindings: BTreeMap::new(),
+            recording_frame_skip: DEFAULT_FRAME_SKIP,
+            recording_max_frames: DEFAULT_MAX_FRAMES,
+        }
+    }
+}
+impl Settings {
+    pub fn brush_kind(&self) -> ElemKind {
+        ElemKind::from_name(&self.brush_element).unwrap_or(ElemKind::Empty)
+    }
+
+    /// The stored grid size if it is one of [`GridSize::ALL`], the default [`GRID_SIZE`] otherwise
+    pub fn grid_size(&self) -> GridSize {
+        let size = GridSize::new(self.grid_width, self.grid_height);
+        if GridSize::ALL.contains(&size) { size } else { GRID_SIZE }
+    }
+
+    /// Loads the settings file, falling back to the defaults if it is missing or unreadable
+    ///
+    /// An unreadable file is kept next to the new one as `settings.toml.bak` instead of being overwritten
+    pub fn load_or_default() -> Self {
+        let path = settings_path();
+        match load_settings(&path) {
+            Ok(settings) => {
+                info!("Loaded settings from {}", path.display());
+                settings
+            }
+            Err(SettingsError::Io(err)) if err.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(err) => {
+                warn!("Using default settings: {err}");
+                if let Err(err) = fs::rename(&path, path.with_extension("toml.bak")) {
+                    warn!("Could not back up {}: {err}", path.display());
+                }
+                Settings::default()
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    UnsupportedVersion(u32),
+}
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Io(err) => write!(f, "could not access the settings file: {err}"),
+            SettingsError::Parse(err) => write!(f, "the settings file is invalid: {err}"),
+            SettingsError::Serialize(err) => write!(f, "could not serialize the settings: {err}"),
+            SettingsError::UnsupportedVersion(version) => write!(f, "settings version {version} is newer than the supported version {SETTINGS_VERSION}"),
+        }
+    }
+}
+impl std::error::Error for SettingsError {}
+impl From<io::Error> for SettingsError {
+    fn from(err: io::Error) -> Self {
+        SettingsError::Io(err)
+    }
+}
+
+/// `settings.toml` in the platform config directory, or in the working directory if there is none
+pub fn settings_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .unwrap_or_default()
+        .join(SETTINGS_FILE)
+}
+
+pub fn parse_settings(text: &str) -> Result<Settings, SettingsError> {
+    let settings: Settings = toml::from_str(text).map_err(SettingsError::Parse)?;
+    if settings.version > SETTINGS_VERSION {
+        return Err(SettingsError::UnsupportedVersion(settings.version))
+    }
+    Ok(Settings { version: SETTINGS_VERSION, ..settings })
+}
+
+pub fn load_settings(path: &Path) -> Result<Settings, SettingsError> {
+    parse_settings(&fs::read_to_string(path)?)
+}
+
+pub fn save_settings(path: &Path, settings: &Settings) -> Result<(), SettingsError> {
+    let text = toml::to_string_pretty(settings).map_err(SettingsError::Serialize)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Loads the [`Settings`] at startup, applies them and writes them back whenever they change
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut bevy::app::App) {
+        app
+        .insert_resource(Settings::load_or_default())
+        .add_systems(Startup, apply_window_settings)
+        .add_systems(Update, remember_window_layout)
+        .add_systems(PostUpdate, apply_display_mode)
+        .add_systems(OnEnter(AppState::InGame), apply_game_defaults.run_if(resource_exists::<NewWorld>))
+        .add_systems(Last, save_settings_on_change);
+    }
+}
+
+fn apply_window_settings(
+    settings: Res<Settings>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+) {
+    window.resolution.set(settings.window_width as f32, settings.window_height as f32);
+    window.mode = settings.display_mode.window_mode();
+}
+
+/// Switches the window when the display mode is changed in the settings
+fn apply_display_mode(
+    settings: Res<Settings>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+) {
+    if settings.is_changed() && DisplayMode::from_window_mode(window.mode) != settings.display_mode {
+        window.mode = settings.display_mode.window_mode();
+    }
+}
+
+/// Keeps the display mode and windowed size in the settings up to date as the user changes them
+fn remember_window_layout(
+    mut resized: EventReader<WindowResized>,
+    mut settings: ResMut<Settings>,
+    window: Single<&Window, With<PrimaryWindow>>,
+) {
+    if resized.read().last().is_none() { return }
+
+    let display_mode = DisplayMode::from_window_mode(window.mode);
+    if settings.display_mode != display_mode {
+        settings.display_mode = display_mode;
+    }
+
+    let (width, height) = (window.width().round() as u32, window.height().round() as u32);
+    if display_mode == DisplayMode::Windowed && (settings.window_width, settings.window_height) != (width, height) {
+        settings.window_width = width;
+        settings.window_height = height;
+    }
+}
+
+/// Every new game starts with the brush and simulation speed chosen in the settings
+fn apply_game_defaults(
+    settings: Res<Settings>,
+    mut selected_elems: ResMut<UserSelectedElements>,
+    mut control: ResMut<SimulationControl>,
+) {
+    selected_elems.kind = settings.brush_kind();
+    selected_elems.radius = settings.brush_radius.max(1);
+    selected_elems.layer = PaintLayer::World;
+    control.set_speed(settings.sim_speed);
+}
+
+/// Sizes a new world by the settings, before its grid is spawned
+pub fn apply_grid_size(settings: Res<Settings>, mut size: ResMut<GridSize>) {
+    *size = settings.grid_size();
+}
+
+/// Writes the settings [`SAVE_DELAY`] after their last change, or right away when the app closes with changes left unsaved
+fn save_settings_on_change(
+    settings: Res<Settings>,
+    time: Res<Time<Real>>,
+    exit: EventReader<AppExit>,
+    mut changed_at: Local<Option<Duration>>,
+) {
+    if settings.is_changed() && !settings.is_added() {
+        *changed_at = Some(time.elapsed());
+    }
+    let Some(last_change) = *changed_at else { return };
+    if time.elapsed() - last_change < SAVE_DELAY && exit.is_empty() { return }
+    *changed_at = None;
+
+    if let Err(err) = save_settings(&settings_path(), &settings) {
+        error!("Could not save settings: {err}");
+    }
+}
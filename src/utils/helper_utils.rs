@@ -1,37 +1,64 @@
-use bevy::{core_pipeline::core_2d::Camera2d, ecs::{ query::With, system::{Res, ResMut, Single}}, input::{keyboard::KeyCode, ButtonInput}, log::error, render::camera::Projection, ui::UiScale, window::{MonitorSelection, Window, WindowMode}};
+use std::time::{SystemTime, UNIX_EPOCH};
+use bevy::{ecs::{event::EventReader, query::With, system::{ResMut, Single}}, ui::UiScale, window::{MonitorSelection, PrimaryWindow, Window, WindowMode, WindowResized}};
+use crate::actions::{Action, ActionInput};
 
-pub fn toggle_resolution(
-    keys: Res<ButtonInput<KeyCode>>,
+/// Window height in logical pixels at which the UI is shown at its natural size
+const UI_REFERENCE_HEIGHT: f32 = 1080.;
+
+/// [`Action::ToggleFullscreen`] switches between windowed and borderless fullscreen, the layout follows through [`fit_ui_to_window`]
+pub fn toggle_fullscreen(
+    actions: ActionInput,
     mut window: Single<&mut Window>,
-    mut query_camera: Single<&mut Projection, With<Camera2d>>, 
-    mut uiscale: ResMut<UiScale>
 ) {
-    if keys.just_pressed(KeyCode::F11) {
-        match query_camera.as_mut() {
-            Projection::Orthographic(ortho) => {
-                match window.mode {
-                    bevy::window::WindowMode::Windowed => {
-
-                        window.mode = WindowMode::BorderlessFullscreen(MonitorSelection::Primary);
-                        ortho.scale = ortho.scale / 2.;
-                        uiscale.0 = uiscale.0 * 2.;
+    if actions.just_pressed(Action::ToggleFullscreen) {
+        window.mode = match window.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+            _ => WindowMode::Windowed,
+        };
+    }
+}
 
-                    }
-                    bevy::window::WindowMode::BorderlessFullscreen(_) => {
+/// UI scale at which menus take the same share of the window at any size
+pub fn ui_scale_for(window: &Window) -> f32 {
+    window.height() / UI_REFERENCE_HEIGHT
+}
 
-                        window.mode = WindowMode::Windowed;
-                        ortho.scale = ortho.scale * 2.;
-                        uiscale.0 = uiscale.0 / 2.;
-                    },
-                    _ => {
-                        error!("Window is in invalid mode")
-                    }
-                };
-                
-            }
-            _ => {
-                error!("Scrolling Error: Projection is not Orthograpic as should be by Default");
-            }
-        }
+pub fn fit_ui_to_window(
+    mut resized: EventReader<WindowResized>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut uiscale: ResMut<UiScale>,
+) {
+    if resized.read().last().is_some() {
+        uiscale.0 = ui_scale_for(&window);
     }
-}
\ No newline at end of file
+}
+
+/// Current UTC time as `YYYYMMDD-HHMMSS-mmm`, used for file names
+pub fn timestamp() -> String {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let day_secs = secs % 86_400;
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}-{:03}",
+        day_secs / 3600,
+        day_secs / 60 % 60,
+        day_secs % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+/// Days since 1970-01-01 to a (year, month, day) date in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
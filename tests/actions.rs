@@ -0,0 +1,105 @@
+//! Key bindings: stored bindings never leave two actions on the same key, only rebound actions are
+//! stored, rebinding swaps with the action that used the key, and the debug overlay keys stay reserved
+
+use std::collections::{BTreeMap, HashSet};
+use bevy::input::{keyboard::KeyCode, mouse::MouseButton};
+use sandfall_mimimi::{actions::{Action, ActionMap, Binding, RebindError}, game::sandworld::debug_overlay::OVERLAY_KEYS};
+
+fn stored(bindings: &[(&str, &str)]) -> BTreeMap<String, String> {
+    bindings.iter().map(|(action, binding)| (action.to_string(), binding.to_string())).collect()
+}
+
+fn assert_no_conflicts(map: &ActionMap) {
+    let bindings: HashSet<Binding> = Action::ALL.into_iter().map(|action| map.get(action)).collect();
+    assert_eq!(bindings.len(), Action::ALL.len(), "{map:?}");
+}
+
+#[test]
+fn defaults_do_not_conflict() {
+    assert_no_conflicts(&ActionMap::default());
+    assert!(ActionMap::default().to_settings().is_empty());
+}
+
+#[test]
+fn stored_bindings_are_applied() {
+    let map = ActionMap::from_settings(&stored(&[("SelectNextElement", "KeyQ"), ("Erase", "MouseBack")]));
+    assert_eq!(map.get(Action::SelectNextElement), Binding::Key(KeyCode::KeyQ));
+    assert_eq!(map.get(Action::Erase), Binding::Mouse(MouseButton::Back));
+    assert_no_conflicts(&map);
+}
+
+#[test]
+fn unknown_names_are_skipped() {
+    let map = ActionMap::from_settings(&stored(&[("Teleport", "KeyQ"), ("FitCamera", "KeyWhatever")]));
+    assert_eq!(map, ActionMap::default());
+}
+
+#[test]
+fn a_binding_taken_by_a_default_goes_back_to_the_default() {
+    // FitCamera keeps its default F, so the earlier SelectNextElement has to give it up
+    let map = ActionMap::from_settings(&stored(&[("SelectNextElement", "KeyF")]));
+    assert_eq!(map.get(Action::SelectNextElement), Action::SelectNextElement.default_binding());
+    assert_eq!(map.get(Action::FitCamera), Binding::Key(KeyCode::KeyF));
+    assert_no_conflicts(&map);
+}
+
+#[test]
+fn two_rebound_actions_on_one_binding_keep_the_earlier_one() {
+    let map = ActionMap::from_settings(&stored(&[("SelectNextElement", "KeyQ"), ("FitCamera", "KeyQ")]));
+    assert_eq!(map.get(Action::SelectNextElement), Binding::Key(KeyCode::KeyQ));
+    assert_eq!(map.get(Action::FitCamera), Action::FitCamera.default_binding());
+    assert_no_conflicts(&map);
+}
+
+#[test]
+fn resets_that_cause_new_conflicts_are_resolved_too() {
+    // Resetting FitCamera to F would clash with SelectNextElement, which then has to go back to M,
+    // which ResetBrushRadius took
+    let map = ActionMap::from_settings(&stored(&[("SelectNextElement", "KeyF"), ("FitCamera", "KeyN"), ("ResetBrushRadius", "KeyM")]));
+    assert_no_conflicts(&map);
+    let map = ActionMap::from_settings(&stored(&[("SelectNextElement", "KeyF"), ("FitCamera", "KeyF"), ("ResetBrushRadius", "KeyM")]));
+    assert_no_conflicts(&map);
+}
+
+#[test]
+fn swapped_defaults_are_kept() {
+    let map = ActionMap::from_settings(&stored(&[("SelectNextElement", "KeyF"), ("FitCamera", "KeyM")]));
+    assert_eq!(map.get(Action::SelectNextElement), Binding::Key(KeyCode::KeyF));
+    assert_eq!(map.get(Action::FitCamera), Binding::Key(KeyCode::KeyM));
+}
+
+#[test]
+fn only_rebound_actions_are_stored() {
+    let mut map = ActionMap::default();
+    map.bind(Action::Screenshot, Binding::Key(KeyCode::KeyP));
+    map.bind(Action::Pause, Action::Pause.default_binding());
+
+    let settings = map.to_settings();
+    assert_eq!(settings, stored(&[("Screenshot", "KeyP")]));
+    assert_eq!(ActionMap::from_settings(&settings), map);
+}
+
+#[test]
+fn rebinding_swaps_with_the_action_using_the_binding() {
+    let mut map = ActionMap::default();
+    let swapped = map.rebind_swapping(Action::SelectNextElement, Binding::Key(KeyCode::KeyF));
+    assert_eq!(swapped, Ok(Some(Action::FitCamera)));
+    assert_eq!(map.get(Action::SelectNextElement), Binding::Key(KeyCode::KeyF));
+    assert_eq!(map.get(Action::FitCamera), Binding::Key(KeyCode::KeyM));
+    assert_no_conflicts(&map);
+
+    assert_eq!(map.rebind_swapping(Action::Screenshot, Binding::Key(KeyCode::KeyP)), Ok(None));
+    assert_no_conflicts(&map);
+}
+
+#[test]
+fn overlay_keys_cannot_be_bound() {
+    for key in OVERLAY_KEYS {
+        let mut map = ActionMap::default();
+        assert_eq!(map.rebind_swapping(Action::Screenshot, Binding::Key(key)), Err(RebindError::Reserved(Binding::Key(key))));
+        assert_eq!(map, ActionMap::default());
+
+        let stored = stored(&[("Screenshot", &Binding::Key(key).to_string())]);
+        assert_eq!(ActionMap::from_settings(&stored), ActionMap::default());
+    }
+}
@@ -0,0 +1,70 @@
+//! Debug overlays: the scan direction they show is the one the simulation step uses, and changed cells,
+//! dirty chunks and activity follow what happened to the grid
+
+use sandfall_mimimi::game::sandworld::{debug_overlay::{changed_cells, decay_activity, dirty_chunks, ACTIVITY_DECAY, CHUNK_SIZE}, main_interaction::scans_left_to_right, ElemKind, SandColor, GRID_SIZE};
+
+const CELLS: usize = (GRID_SIZE.width * GRID_SIZE.height) as usize;
+
+fn index(x: u32, y: u32) -> usize {
+    (y * GRID_SIZE.width + x) as usize
+}
+
+/// Every cell unchanged but the given ones
+fn changed_at(cells: &[(u32, u32)]) -> Vec<bool> {
+    let mut changed = vec![false; CELLS];
+    for (x, y) in cells {
+        changed[index(*x, *y)] = true;
+    }
+    changed
+}
+
+#[test]
+fn scan_direction_alternates_by_row_and_tick() {
+    for tick in 0..4 {
+        for y in 0..8 {
+            assert_ne!(scans_left_to_right(y, tick), scans_left_to_right(y + 1, tick), "rows {y} and {} at tick {tick}", y + 1);
+            assert_ne!(scans_left_to_right(y, tick), scans_left_to_right(y, tick + 1), "row {y} at ticks {tick} and {}", tick + 1);
+        }
+    }
+}
+
+#[test]
+fn changed_cells_are_the_ones_holding_another_element() {
+    let previous = vec![ElemKind::Empty; CELLS];
+    let mut current = previous.clone();
+    current[index(3, 4)] = ElemKind::Stone;
+    current[index(100, 150)] = ElemKind::Sand(SandColor::Blue);
+
+    assert_eq!(changed_cells(&previous, &current), changed_at(&[(3, 4), (100, 150)]));
+    assert_eq!(changed_cells(&current, &current), vec![false; CELLS]);
+}
+
+#[test]
+fn dirty_chunks_hold_a_changed_cell() {
+    assert!(dirty_chunks(&vec![false; CELLS], GRID_SIZE).is_empty());
+
+    let last = (GRID_SIZE.width - 1, GRID_SIZE.height - 1);
+    let changed = changed_at(&[(0, 0), (CHUNK_SIZE - 1, CHUNK_SIZE - 1), (CHUNK_SIZE, 0), (3 * CHUNK_SIZE + 2, CHUNK_SIZE), last]);
+    let last_chunk = (last.0 / CHUNK_SIZE, last.1 / CHUNK_SIZE);
+    assert_eq!(dirty_chunks(&changed, GRID_SIZE), vec![(0, 0), (1, 0), (3, 1), last_chunk]);
+}
+
+#[test]
+fn activity_builds_up_while_cells_change_and_fades_after() {
+    let changed = changed_at(&[(5, 5)]);
+    let unchanged = vec![false; CELLS];
+    let mut activity = vec![0.; CELLS];
+
+    decay_activity(&mut activity, &changed);
+    assert!((activity[index(5, 5)] - (1. - ACTIVITY_DECAY)).abs() < 1e-6);
+    assert_eq!(activity[index(6, 5)], 0.);
+
+    for _ in 0..200 {
+        decay_activity(&mut activity, &changed);
+    }
+    assert!(activity[index(5, 5)] > 0.99 && activity[index(5, 5)] <= 1.);
+
+    let busy = activity[index(5, 5)];
+    decay_activity(&mut activity, &unchanged);
+    assert!((activity[index(5, 5)] - busy * ACTIVITY_DECAY).abs() < 1e-6);
+}
@@ -0,0 +1,76 @@
+//! Undo and redo of brush strokes: strokes come back exactly, undo leaves alone what the simulation moved
+//! into the painted cells, and the history stays within its memory limit
+
+use sandfall_mimimi::game::sandworld::{edit_history::{EditHistory, HistoryMode, DEFAULT_HISTORY_BYTES}, Elem, ElemKind, ElemPos, GridCells, SandColor};
+
+const SAND: ElemKind = ElemKind::Sand(SandColor::Yellow);
+
+/// Paints `kind` over `positions` as a single stroke, recording it like the brush does
+fn paint(history: &mut EditHistory, grid_cells: &mut GridCells, positions: &[ElemPos], kind: ElemKind) {
+    history.begin_stroke(grid_cells);
+    for &pos in positions {
+        let before = grid_cells.get_elem_at(pos).unwrap();
+        let after = Elem::new(kind, false).seeded_at(pos);
+        grid_cells.set_elem_at(pos, after);
+        history.record(pos, before, after);
+    }
+    history.end_stroke();
+}
+
+fn kind_at(grid_cells: &GridCells, x: u32, y: u32) -> ElemKind {
+    grid_cells.get_elem_at(ElemPos::new(x, y)).unwrap().kind()
+}
+
+#[test]
+fn undo_and_redo_restore_a_stroke() {
+    for mode in [HistoryMode::Diff, HistoryMode::Snapshot] {
+        let mut history = EditHistory::new(mode, DEFAULT_HISTORY_BYTES);
+        let mut grid_cells = GridCells::new_empty();
+        let stroke: Vec<ElemPos> = (10..20).map(|x| ElemPos::new(x, 30)).collect();
+        paint(&mut history, &mut grid_cells, &stroke, SAND);
+
+        assert!(history.undo(&mut grid_cells), "{mode:?}");
+        assert!(stroke.iter().all(|pos| grid_cells.get_elem_at(*pos).unwrap().kind() == ElemKind::Empty), "{mode:?}");
+        assert!(!history.undo(&mut grid_cells), "{mode:?}: nothing left to undo");
+
+        assert!(history.redo(&mut grid_cells), "{mode:?}");
+        assert!(stroke.iter().all(|pos| grid_cells.get_elem_at(*pos).unwrap().kind() == SAND), "{mode:?}");
+        assert!(!history.redo(&mut grid_cells), "{mode:?}: nothing left to redo");
+    }
+}
+
+#[test]
+fn undo_keeps_what_moved_into_the_painted_cells() {
+    let mut history = EditHistory::default();
+    let mut grid_cells = GridCells::new_empty();
+    paint(&mut history, &mut grid_cells, &[ElemPos::new(5, 5), ElemPos::new(6, 5)], SAND);
+
+    // The first grain fell away and stone took its place, the second stayed put
+    grid_cells.set_elem_at(ElemPos::new(5, 6), Elem::new(SAND, false));
+    grid_cells.set_elem_at(ElemPos::new(5, 5), Elem::new(ElemKind::Stone, false));
+
+    history.undo(&mut grid_cells);
+    assert_eq!(kind_at(&grid_cells, 5, 5), ElemKind::Stone);
+    assert_eq!(kind_at(&grid_cells, 5, 6), SAND);
+    assert_eq!(kind_at(&grid_cells, 6, 5), ElemKind::Empty);
+
+    history.redo(&mut grid_cells);
+    assert_eq!(kind_at(&grid_cells, 5, 5), ElemKind::Stone);
+    assert_eq!(kind_at(&grid_cells, 6, 5), SAND);
+}
+
+#[test]
+fn history_stays_within_its_memory_limit() {
+    let mut history = EditHistory::new(HistoryMode::Snapshot, DEFAULT_HISTORY_BYTES);
+    let mut grid_cells = GridCells::new_empty();
+    for i in 0..200 {
+        let kind = if i % 2 == 0 { SAND } else { ElemKind::Empty };
+        paint(&mut history, &mut grid_cells, &[ElemPos::new(0, 0)], kind);
+        assert!(history.used_bytes() <= DEFAULT_HISTORY_BYTES, "{} bytes after {} strokes", history.used_bytes(), i + 1);
+    }
+    assert_eq!(history.undo_len(), DEFAULT_HISTORY_BYTES / grid_cells.size_bytes());
+
+    // The oldest strokes were dropped, the newest still undo
+    assert!(history.undo(&mut grid_cells));
+    assert_eq!(kind_at(&grid_cells, 0, 0), SAND);
+}
@@ -0,0 +1,116 @@
+//! Gamepad controls driven by synthetic gamepad events in an [`App`] without a window or renderer:
+//! the shoulder buttons cycle the selected element, the left stick moves the painting cursor and
+//! the D-pad and South button navigate and press menu buttons
+
+use std::time::Duration;
+use bevy::{app::{App, PreUpdate, Update}, ecs::{entity::Entity, event::Events, schedule::IntoScheduleConfigs}, input::{gamepad::{GamepadAxis, GamepadButton, GamepadConnection, GamepadConnectionEvent, RawGamepadAxisChangedEvent, RawGamepadButtonChangedEvent, RawGamepadEvent}, InputPlugin, InputSystem}, math::Vec2, render::view::InheritedVisibility, time::TimeUpdateStrategy, transform::components::GlobalTransform, ui::{widget::Button, ComputedNode, Interaction}, utils::default, window::{PrimaryWindow, Window, WindowResolution}, MinimalPlugins};
+use sandfall_mimimi::{actions::ActionMap, game::{gamepad_cursor::{move_virtual_cursor, VirtualCursor}, sandworld::{user_element_interraction::{user_selects_element, UserEdit, UserSelectedElements}, ElemKind, SandColor}}, menu::{menu::RebindState, navigation::{navigate_menu, MenuFocus}}};
+
+fn input_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, InputPlugin));
+    app
+}
+
+fn connect_gamepad(app: &mut App) -> Entity {
+    let gamepad = app.world_mut().spawn_empty().id();
+    app.world_mut().send_event(GamepadConnectionEvent::new(gamepad, GamepadConnection::Connected {
+        name: "Test gamepad".into(),
+        vendor_id: None,
+        product_id: None,
+    }));
+    app.update();
+    gamepad
+}
+
+fn set_button(app: &mut App, gamepad: Entity, button: GamepadButton, value: f32) {
+    app.world_mut().send_event(RawGamepadEvent::Button(RawGamepadButtonChangedEvent::new(gamepad, button, value)));
+    app.update();
+}
+
+/// Presses and releases the button, one frame each
+fn tap(app: &mut App, gamepad: Entity, button: GamepadButton) {
+    set_button(app, gamepad, button, 1.);
+    set_button(app, gamepad, button, 0.);
+}
+
+fn drain_edits(app: &mut App) -> Vec<UserEdit> {
+    app.world_mut().resource_mut::<Events<UserEdit>>().drain().collect()
+}
+
+#[test]
+fn shoulder_buttons_cycle_elements() {
+    let mut app = input_app();
+    app.init_resource::<ActionMap>()
+        .insert_resource(UserSelectedElements::single(ElemKind::Empty))
+        .add_event::<UserEdit>()
+        .add_systems(Update, user_selects_element);
+    let gamepad = connect_gamepad(&mut app);
+
+    tap(&mut app, gamepad, GamepadButton::RightTrigger);
+    assert_eq!(drain_edits(&mut app), [UserEdit::SelectElement(ElemKind::Sand(SandColor::Yellow))]);
+
+    tap(&mut app, gamepad, GamepadButton::LeftTrigger);
+    assert_eq!(drain_edits(&mut app), [UserEdit::SelectElement(ElemKind::Stone)]);
+}
+
+#[test]
+fn left_stick_moves_the_virtual_cursor_inside_the_window() {
+    let mut app = input_app();
+    app.init_resource::<VirtualCursor>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+        .add_systems(Update, move_virtual_cursor);
+    app.world_mut().spawn((Window { resolution: WindowResolution::new(800., 600.), ..default() }, PrimaryWindow));
+    let gamepad = connect_gamepad(&mut app);
+    assert_eq!(app.world().resource::<VirtualCursor>().position, None);
+
+    app.world_mut().send_event(RawGamepadEvent::Axis(RawGamepadAxisChangedEvent::new(gamepad, GamepadAxis::LeftStickX, 1.)));
+    app.update();
+    app.update();
+    let position = app.world().resource::<VirtualCursor>().position.expect("the stick shows the cursor");
+    assert!(position.x > 400., "the cursor moved right from the middle, it is at {position}");
+    assert_eq!(position.y, 300.);
+
+    for _ in 0..50 {
+        app.update();
+    }
+    assert_eq!(app.world().resource::<VirtualCursor>().position, Some(Vec2::new(800., 300.)));
+}
+
+#[test]
+fn dpad_moves_focus_and_south_presses_the_focused_button() {
+    let mut app = input_app();
+    app.init_resource::<MenuFocus>()
+        .init_resource::<RebindState>()
+        .add_systems(PreUpdate, navigate_menu.after(InputSystem));
+
+    let mut spawn_button = |y: f32, size: Vec2| app.world_mut().spawn((
+        Button,
+        ComputedNode { size, ..default() },
+        GlobalTransform::from_xyz(400., y, 0.),
+        InheritedVisibility::VISIBLE,
+    )).id();
+    let top = spawn_button(100., Vec2::new(200., 50.));
+    // Hidden like a button with `Display::None`, navigation skips it
+    let hidden = spawn_button(200., Vec2::ZERO);
+    let bottom = spawn_button(300., Vec2::new(200., 50.));
+    let gamepad = connect_gamepad(&mut app);
+    assert_eq!(app.world().resource::<MenuFocus>().focused, None);
+
+    tap(&mut app, gamepad, GamepadButton::DPadDown);
+    assert_eq!(app.world().resource::<MenuFocus>().focused, Some(top), "the first input focuses the first button");
+
+    tap(&mut app, gamepad, GamepadButton::DPadDown);
+    assert_eq!(app.world().resource::<MenuFocus>().focused, Some(bottom));
+    assert_ne!(app.world().resource::<MenuFocus>().focused, Some(hidden));
+
+    set_button(&mut app, gamepad, GamepadButton::South, 1.);
+    assert_eq!(app.world().get::<Interaction>(bottom), Some(&Interaction::Pressed));
+    assert_eq!(app.world().get::<Interaction>(top), Some(&Interaction::None));
+
+    set_button(&mut app, gamepad, GamepadButton::South, 0.);
+    assert_eq!(app.world().get::<Interaction>(bottom), Some(&Interaction::None));
+
+    tap(&mut app, gamepad, GamepadButton::DPadUp);
+    assert_eq!(app.world().resource::<MenuFocus>().focused, Some(top));
+}
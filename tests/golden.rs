@@ -0,0 +1,70 @@
+//! Golden-image regression tests: every scenario in `tests/scenarios` is simulated and its map window is
+//! compared with the matching `.golden` file. Run with `BLESS=1` to write the current results as the new goldens
+
+use std::{env, fs, path::{Path, PathBuf}};
+use sandfall_mimimi::game::sandworld::scenario::Scenario;
+
+fn scenario_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scenarios")
+}
+
+/// Lists the rows that differ between the golden and the simulated window
+fn row_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let mut diff = String::new();
+    for row in 0..expected.len().max(actual.len()) {
+        let (want, got) = (expected.get(row).copied().unwrap_or(""), actual.get(row).copied().unwrap_or(""));
+        if want != got {
+            diff.push_str(&format!("row {row:>3} expected {want}\n          got      {got}\n"));
+        }
+    }
+    diff
+}
+
+fn check_golden(name: &str) {
+    let scenario_path = scenario_dir().join(format!("{name}.txt"));
+    let golden_path = scenario_dir().join(format!("{name}.golden"));
+
+    let scenario = Scenario::load(&scenario_path).unwrap_or_else(|err| panic!("{}: {err}", scenario_path.display()));
+    let grid_cells = scenario.run();
+    let actual = scenario.render(&grid_cells);
+
+    let escaped = scenario.cells_outside_window(&grid_cells);
+    assert_eq!(escaped, 0, "{name}: {escaped} cells left the map window, enlarge the map or add walls");
+
+    if env::var_os("BLESS").is_some() {
+        fs::write(&golden_path, &actual).unwrap();
+        return
+    }
+
+    let expected = fs::read_to_string(&golden_path)
+        .unwrap_or_else(|_| panic!("{name}: no golden at {}, run with BLESS=1 to create it", golden_path.display()));
+    if expected != actual {
+        panic!(
+            "{name}: the grid after {} ticks differs from {}\n{}\nRerun with BLESS=1 if the change is intended",
+            scenario.ticks,
+            golden_path.display(),
+            row_diff(&expected, &actual),
+        );
+    }
+}
+
+macro_rules! golden_tests {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[test]
+            fn $name() {
+                check_golden(stringify!($name));
+            }
+        )*
+    };
+}
+
+golden_tests!(
+    sand_pile_angle,
+    sand_falls_past_stone_edge,
+    sand_alternates_left_right,
+    avalanche,
+);
@@ -0,0 +1,64 @@
+//! Light maps spread from emitters through empty cells and are stopped by solids, the lighting pass dims
+//! unlit pixels and puts a halo into the transparent ones around emitters
+
+use bevy::color::{Alpha, Color};
+use sandfall_mimimi::{game::sandworld::{lighting::{LightMap, MAX_LIGHT}, Elem, ElemKind, ElemPos, GridCells}, settings::LightingMode};
+
+const SOURCE: ElemPos = ElemPos { x: 50, y: 50 };
+
+fn lit(grid_cells: &GridCells) -> LightMap {
+    LightMap::from_sources(grid_cells, [(SOURCE, MAX_LIGHT)])
+}
+
+#[test]
+fn light_fades_one_level_per_cell() {
+    let light_map = lit(&GridCells::new_empty());
+
+    assert_eq!(light_map.light_at(SOURCE), 1.);
+    for distance in 1..=MAX_LIGHT as u32 {
+        let expected = (MAX_LIGHT as u32 - distance) as f32 / MAX_LIGHT as f32;
+        assert_eq!(light_map.light_at(ElemPos::new(SOURCE.x + distance, SOURCE.y)), expected, "{distance} cells right");
+        assert_eq!(light_map.light_at(ElemPos::new(SOURCE.x, SOURCE.y - distance)), expected, "{distance} cells up");
+    }
+    assert_eq!(light_map.light_at(ElemPos::new(SOURCE.x + 4, SOURCE.y + 4)), (MAX_LIGHT - 8) as f32 / MAX_LIGHT as f32);
+}
+
+#[test]
+fn solids_are_lit_but_block_the_light() {
+    let mut grid_cells = GridCells::new_empty();
+    // A stone wall right of the source, taller than the light reaches
+    for y in SOURCE.y - MAX_LIGHT as u32..=SOURCE.y + MAX_LIGHT as u32 {
+        grid_cells.set_elem_at(ElemPos::new(SOURCE.x + 2, y), Elem::new(ElemKind::Stone, false));
+    }
+    let light_map = lit(&grid_cells);
+
+    assert!(light_map.light_at(ElemPos::new(SOURCE.x + 2, SOURCE.y)) > 0., "the wall is lit");
+    assert_eq!(light_map.light_at(ElemPos::new(SOURCE.x + 3, SOURCE.y)), 0., "nothing gets through the wall");
+}
+
+#[test]
+fn lighting_modes() {
+    let light_map = lit(&GridCells::new_empty());
+    let stone = ElemKind::Stone.get_base_color(Default::default());
+    let dark = ElemPos::new(SOURCE.x + 100, SOURCE.y);
+    let near = ElemPos::new(SOURCE.x + 1, SOURCE.y);
+    let transparent = Color::srgba(0., 0., 0., 0.);
+
+    assert_eq!(light_map.lit_color(stone, dark, LightingMode::Off), stone);
+    let dimmed = light_map.lit_color(stone, dark, LightingMode::Lit).to_linear();
+    let brightened = light_map.lit_color(stone, near, LightingMode::Lit).to_linear();
+    assert!(dimmed.red < stone.to_linear().red, "unlit cells are darker");
+    assert!(brightened.red > dimmed.red, "cells near the source are brighter");
+
+    assert_eq!(light_map.lit_color(transparent, near, LightingMode::Lit), transparent);
+    assert!(light_map.lit_color(transparent, near, LightingMode::Glow).alpha() > 0., "empty cells glow");
+    assert_eq!(light_map.lit_color(transparent, dark, LightingMode::Glow), transparent);
+}
+
+#[test]
+fn worlds_without_emitters_are_left_unlit() {
+    let mut grid_cells = GridCells::new_empty();
+    grid_cells.set_elem_at(SOURCE, Elem::new(ElemKind::Stone, false));
+    assert!(ElemKind::ALL.iter().all(|kind| kind.light_emission() == 0), "update this test once an element gives off light");
+    assert!(LightMap::compute(&grid_cells).is_none());
+}
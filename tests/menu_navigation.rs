@@ -0,0 +1,99 @@
+//! Keyboard navigation of menu buttons driven by synthetic key events in an [`App`] without a window or renderer:
+//! Tab and the arrow keys move the focus, Enter presses the focused button
+
+use bevy::{app::{App, PreUpdate}, ecs::{entity::Entity, schedule::IntoScheduleConfigs}, input::{keyboard::{Key, KeyCode, KeyboardInput, NativeKey}, ButtonState, InputPlugin, InputSystem}, math::Vec2, render::view::InheritedVisibility, transform::components::GlobalTransform, ui::{widget::Button, ComputedNode, Interaction}, utils::default, MinimalPlugins};
+use sandfall_mimimi::menu::{menu::RebindState, navigation::{navigate_menu, MenuFocus}};
+
+/// A 2x2 grid of buttons, returned in reading order
+fn menu_app() -> (App, [Entity; 4]) {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, InputPlugin))
+        .init_resource::<MenuFocus>()
+        .init_resource::<RebindState>()
+        .add_systems(PreUpdate, navigate_menu.after(InputSystem));
+
+    let buttons = [(100., 100.), (400., 100.), (100., 200.), (400., 200.)].map(|(x, y)| {
+        app.world_mut().spawn((
+            Button,
+            ComputedNode { size: Vec2::new(200., 50.), ..default() },
+            GlobalTransform::from_xyz(x, y, 0.),
+            InheritedVisibility::VISIBLE,
+        )).id()
+    });
+    app.update();
+    (app, buttons)
+}
+
+fn set_key(app: &mut App, key_code: KeyCode, state: ButtonState) {
+    let window = app.world_mut().spawn_empty().id();
+    app.world_mut().send_event(KeyboardInput {
+        key_code,
+        logical_key: Key::Unidentified(NativeKey::Unidentified),
+        state,
+        text: None,
+        repeat: false,
+        window,
+    });
+    app.update();
+}
+
+/// Presses and releases the key, one frame each
+fn tap(app: &mut App, key_code: KeyCode) {
+    set_key(app, key_code, ButtonState::Pressed);
+    set_key(app, key_code, ButtonState::Released);
+}
+
+fn focused(app: &App) -> Option<Entity> {
+    app.world().resource::<MenuFocus>().focused
+}
+
+#[test]
+fn tab_walks_the_buttons_in_reading_order() {
+    let (mut app, [top_left, top_right, bottom_left, bottom_right]) = menu_app();
+    assert_eq!(focused(&app), None);
+
+    tap(&mut app, KeyCode::Tab);
+    assert_eq!(focused(&app), Some(top_left), "the first input focuses the first button");
+    tap(&mut app, KeyCode::Tab);
+    assert_eq!(focused(&app), Some(top_right));
+    tap(&mut app, KeyCode::Tab);
+    assert_eq!(focused(&app), Some(bottom_left));
+    tap(&mut app, KeyCode::Tab);
+    assert_eq!(focused(&app), Some(bottom_right));
+    tap(&mut app, KeyCode::Tab);
+    assert_eq!(focused(&app), Some(top_left), "tab wraps around");
+
+    set_key(&mut app, KeyCode::ShiftLeft, ButtonState::Pressed);
+    tap(&mut app, KeyCode::Tab);
+    assert_eq!(focused(&app), Some(bottom_right), "shift+tab goes backwards");
+}
+
+#[test]
+fn arrows_move_to_the_nearest_button_in_their_direction() {
+    let (mut app, [top_left, top_right, bottom_left, bottom_right]) = menu_app();
+
+    tap(&mut app, KeyCode::ArrowDown);
+    assert_eq!(focused(&app), Some(top_left));
+    tap(&mut app, KeyCode::ArrowDown);
+    assert_eq!(focused(&app), Some(bottom_left));
+    tap(&mut app, KeyCode::ArrowRight);
+    assert_eq!(focused(&app), Some(bottom_right));
+    tap(&mut app, KeyCode::ArrowUp);
+    assert_eq!(focused(&app), Some(top_right));
+    tap(&mut app, KeyCode::ArrowUp);
+    assert_eq!(focused(&app), Some(top_right), "nothing is above the top row");
+}
+
+#[test]
+fn enter_presses_the_focused_button_until_released() {
+    let (mut app, [top_left, top_right, ..]) = menu_app();
+
+    tap(&mut app, KeyCode::Tab);
+    tap(&mut app, KeyCode::Tab);
+    set_key(&mut app, KeyCode::Enter, ButtonState::Pressed);
+    assert_eq!(app.world().get::<Interaction>(top_right), Some(&Interaction::Pressed));
+    assert_eq!(app.world().get::<Interaction>(top_left), Some(&Interaction::None));
+
+    set_key(&mut app, KeyCode::Enter, ButtonState::Released);
+    assert_eq!(app.world().get::<Interaction>(top_right), Some(&Interaction::None));
+}
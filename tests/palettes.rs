@@ -0,0 +1,48 @@
+//! Colour palettes and sand patterns: every palette keeps the sand colours apart, and patterns only
+//! darken the cells of their texture
+
+use bevy::color::ColorToPacked;
+use sandfall_mimimi::{game::sandworld::{draw_image::GridStyle, Elem, ElemKind, ElemPos, SandColor}, settings::ColorPalette};
+
+const SANDS: [SandColor; 4] = [SandColor::Yellow, SandColor::Red, SandColor::Blue, SandColor::Green];
+/// Smallest sRGB distance, summed over the channels, between two sand colours of a palette
+const MIN_DISTANCE: i32 = 60;
+
+#[test]
+fn every_palette_keeps_the_sand_colours_apart() {
+    for palette in ColorPalette::ALL {
+        let colors = SANDS.map(|sand| ElemKind::Sand(sand).get_base_color(palette).to_srgba().to_u8_array());
+        for (i, a) in colors.iter().enumerate() {
+            for (j, b) in colors.iter().enumerate().skip(i + 1) {
+                let distance: i32 = (0..3).map(|c| (a[c] as i32 - b[c] as i32).abs()).sum();
+                assert!(distance >= MIN_DISTANCE, "{palette:?}: {:?} and {:?} are only {distance} apart", SANDS[i], SANDS[j]);
+            }
+        }
+    }
+}
+
+#[test]
+fn patterns_darken_only_their_texture() {
+    let plain = GridStyle { palette: ColorPalette::Standard, ..GridStyle::default() };
+    let patterned = GridStyle { patterns: true, ..plain };
+
+    for sand in SANDS {
+        let mut marked = 0;
+        for x in 0..12 {
+            for y in 0..12 {
+                let pos = ElemPos::new(x, y);
+                let elem = Elem::new(ElemKind::Sand(sand), false).seeded_at(pos);
+                let (without, with) = (plain.cell_color(elem, pos, 0), patterned.cell_color(elem, pos, 0));
+                if sand.has_pattern_at(pos) {
+                    marked += 1;
+                    assert_ne!(without, with, "{sand:?} at {pos:?} is part of the pattern");
+                } else {
+                    assert_eq!(without, with, "{sand:?} at {pos:?} is outside the pattern");
+                }
+            }
+        }
+        assert_eq!(marked == 0, sand == SandColor::Yellow, "{sand:?} has {marked} patterned cells");
+    }
+    let stone = Elem::new(ElemKind::Stone, false);
+    assert_eq!(patterned.cell_color(stone, ElemPos::new(0, 0), 0), plain.cell_color(stone, ElemPos::new(0, 0), 0));
+}
@@ -0,0 +1,123 @@
+//! Replay files: the start world, wall and recorded edits survive a round trip, files written before the
+//! wall was stored still load, unknown values are rejected instead of being read as some other edit, and playing
+//! a recording back ends on the world it was recorded on
+
+use bevy::{app::{App, Update}, ecs::system::{Res, ResMut, RunSystemOnce, Single}, MinimalPlugins};
+use sandfall_mimimi::game::{sandworld::{edit_history::{EditHistory, HistoryMode}, replay::{decode_replay, encode_replay, Replay, ReplayFileError, ReplayState}, simulation_control::SimulationControl, user_element_interraction::{apply_user_edits, PaintLayer, UserEdit, UserSelectedElements}, wall_layer::WallCells, world_file::{decode_world, encode_world}, ElemKind, ElemPos, GridCells, SandColor, GRID_SIZE}, stats::StatEvent};
+
+fn wall_cells() -> WallCells {
+    let mut wall_cells = WallCells::default();
+    wall_cells.set_kind_at(ElemPos::new(3, 4), ElemKind::Stone);
+    wall_cells
+}
+
+fn sample_replay() -> Replay {
+    Replay {
+        world: encode_world(&GridCells::new_empty(), 3, 10),
+        wall: encode_world(&wall_cells().to_grid_cells(), 3, 10),
+        edits: vec![
+            (10, UserEdit::SelectElement(ElemKind::Stone)),
+            (10, UserEdit::SetRadius(4)),
+            (10, UserEdit::SetLayer(PaintLayer::Wall)),
+            (10, UserEdit::SetHistoryMode(HistoryMode::Snapshot)),
+            (12, UserEdit::Paint { from: None, to: ElemPos::new(5, 6) }),
+            (13, UserEdit::Paint { from: Some(ElemPos::new(5, 6)), to: ElemPos::new(7, 6) }),
+            (14, UserEdit::EndStroke),
+            (15, UserEdit::Undo),
+            (16, UserEdit::Redo),
+        ],
+    }
+}
+
+#[test]
+fn replays_survive_a_round_trip() {
+    let replay = sample_replay();
+    let decoded = decode_replay(&encode_replay(&replay)).unwrap();
+    assert_eq!(decoded.world, replay.world);
+    assert_eq!(decoded.wall, replay.wall);
+    assert_eq!(decoded.edits, replay.edits);
+}
+
+#[test]
+fn unknown_modes_and_layers_are_rejected() {
+    for (edit, tag) in [(UserEdit::SetHistoryMode(HistoryMode::Diff), 6), (UserEdit::SetLayer(PaintLayer::World), 7)] {
+        let mut bytes = encode_replay(&Replay { edits: vec![(0, edit)], ..sample_replay() });
+        // The value is the last byte, right after the tag
+        *bytes.last_mut().unwrap() = 2;
+        assert!(
+            matches!(decode_replay(&bytes), Err(ReplayFileError::InvalidValue { tag: found, value: 2 }) if found == tag),
+            "edit {tag} with value 2",
+        );
+    }
+}
+
+#[test]
+fn version_1_replays_load_with_an_empty_wall() {
+    let replay = sample_replay();
+    let current = encode_replay(&replay);
+    // Version 1 had no wall section between the world and the edits
+    let wall_start = 4 + 2 + 4 + replay.world.len();
+    let mut bytes = [&current[..wall_start], &current[wall_start + 4 + replay.wall.len()..]].concat();
+    bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+
+    let decoded = decode_replay(&bytes).unwrap();
+    assert_eq!(decoded.world, replay.world);
+    assert_eq!(decoded.edits, replay.edits);
+    let wall = WallCells::from_grid_cells(&decode_world(&decoded.wall).unwrap().to_grid_cells(GRID_SIZE));
+    assert_eq!(wall.get_kind_at(ElemPos::new(3, 4)), Some(ElemKind::Empty));
+}
+
+fn edit_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(UserSelectedElements::single(ElemKind::Sand(SandColor::Red)))
+        .init_resource::<EditHistory>()
+        .init_resource::<ReplayState>()
+        .init_resource::<SimulationControl>()
+        .add_event::<UserEdit>()
+        .add_event::<StatEvent>()
+        .add_systems(Update, apply_user_edits);
+    app.world_mut().spawn(GridCells::new_empty());
+    app
+}
+
+fn send(app: &mut App, edits: impl IntoIterator<Item = UserEdit>) {
+    for edit in edits {
+        app.world_mut().send_event(edit);
+    }
+    app.update();
+}
+
+fn grid_kinds(app: &mut App) -> Vec<ElemKind> {
+    let grid_cells = app.world_mut().query::<&GridCells>().single(app.world()).unwrap();
+    grid_cells.cells.iter().map(|elem| elem.kind()).collect()
+}
+
+#[test]
+fn undo_cannot_reach_strokes_from_before_the_recording() {
+    let mut app = edit_app();
+    send(&mut app, [UserEdit::Paint { from: Some(ElemPos::new(10, 20)), to: ElemPos::new(14, 20) }, UserEdit::EndStroke]);
+
+    app.world_mut().run_system_once(|
+        mut replay: ResMut<ReplayState>,
+        grid: Single<(&GridCells, &WallCells)>,
+        control: Res<SimulationControl>,
+        selection: Res<UserSelectedElements>,
+        mut history: ResMut<EditHistory>,
+    | {
+        let (grid_cells, wall_cells) = *grid;
+        replay.start_recording(grid_cells, wall_cells, &control, &selection, &mut history);
+    }).unwrap();
+    send(&mut app, [UserEdit::Undo, UserEdit::Paint { from: None, to: ElemPos::new(30, 30) }, UserEdit::EndStroke, UserEdit::Undo]);
+    let grid_cells = app.world_mut().query::<&GridCells>().single(app.world()).unwrap();
+    assert_eq!(grid_cells.get_elem_at(ElemPos::new(12, 20)).unwrap().kind(), ElemKind::Sand(SandColor::Red), "the stroke from before the recording stays");
+    let recorded_end = grid_kinds(&mut app);
+
+    let replay = app.world_mut().resource_mut::<ReplayState>().stop_recording().unwrap();
+    let start = decode_world(&replay.world).unwrap().to_grid_cells(GRID_SIZE);
+    *app.world_mut().query::<&mut GridCells>().single_mut(app.world_mut()).unwrap() = start;
+    app.world_mut().resource_mut::<EditHistory>().clear();
+    send(&mut app, replay.edits.into_iter().map(|(_, edit)| edit));
+
+    assert!(grid_kinds(&mut app) == recorded_end, "playback ends on the recorded world");
+}
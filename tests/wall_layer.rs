@@ -0,0 +1,80 @@
+//! The wall layer: painted through the same edits as the world once selected, left out of the undo history
+//! and drawn behind the simulated cells
+
+use bevy::{app::{App, Update}, MinimalPlugins};
+use sandfall_mimimi::game::{sandworld::{draw_image::GridStyle, edit_history::EditHistory, replay::ReplayState, simulation_control::SimulationControl, user_element_interraction::{apply_user_edits, PaintLayer, UserEdit, UserSelectedElements}, wall_layer::WallCells, Elem, ElemKind, ElemPos, GridCells, SandColor}, stats::StatEvent};
+
+const SAND: ElemKind = ElemKind::Sand(SandColor::Red);
+
+fn edit_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(UserSelectedElements::single(SAND))
+        .init_resource::<EditHistory>()
+        .init_resource::<ReplayState>()
+        .init_resource::<SimulationControl>()
+        .add_event::<UserEdit>()
+        .add_event::<StatEvent>()
+        .add_systems(Update, apply_user_edits);
+    app.world_mut().spawn(GridCells::new_empty());
+    app
+}
+
+fn send(app: &mut App, edits: impl IntoIterator<Item = UserEdit>) {
+    for edit in edits {
+        app.world_mut().send_event(edit);
+    }
+    app.update();
+}
+
+#[test]
+fn painting_the_wall_leaves_the_world_alone() {
+    let mut app = edit_app();
+    let (from, to) = (ElemPos::new(10, 20), ElemPos::new(14, 20));
+
+    send(&mut app, [UserEdit::SetLayer(PaintLayer::Wall), UserEdit::Paint { from: Some(from), to }, UserEdit::EndStroke]);
+
+    let (grid_cells, wall_cells) = app.world_mut().query::<(&GridCells, &WallCells)>().single(app.world()).unwrap();
+    for x in 11..=14 {
+        let pos = ElemPos::new(x, 20);
+        assert_eq!(wall_cells.get_kind_at(pos), Some(SAND), "the wall is painted at {pos:?}");
+        assert_eq!(grid_cells.get_elem_at(pos).unwrap().kind(), ElemKind::Empty, "the world is untouched at {pos:?}");
+    }
+    let undone = app.world_mut().resource_mut::<EditHistory>().undo(&mut GridCells::new_empty());
+    assert!(!undone, "wall strokes are not undoable");
+
+    send(&mut app, [UserEdit::SetLayer(PaintLayer::World), UserEdit::Paint { from: None, to }]);
+    let grid_cells = app.world_mut().query::<&GridCells>().single(app.world()).unwrap();
+    assert_eq!(grid_cells.get_elem_at(to).unwrap().kind(), SAND);
+}
+
+#[test]
+fn simulated_cells_are_drawn_over_the_wall() {
+    let (over, behind, bare) = (ElemPos::new(5, 5), ElemPos::new(6, 5), ElemPos::new(7, 5));
+    let mut grid_cells = GridCells::new_empty();
+    let mut wall_cells = WallCells::default();
+    for pos in [over, behind] {
+        wall_cells.set_kind_at(pos, ElemKind::Stone);
+    }
+    let stone = Elem::new(ElemKind::Stone, false);
+    let sand = Elem::new(SAND, false).seeded_at(over);
+    grid_cells.set_elem_at(over, sand);
+
+    let style = GridStyle::default();
+    assert_eq!(style.pixel_color(&grid_cells, &wall_cells, over, 0), style.cell_color(sand, over, 0));
+    assert_eq!(Some(style.pixel_color(&grid_cells, &wall_cells, behind, 0)), wall_cells.color_at(behind, style.palette));
+    assert_ne!(style.pixel_color(&grid_cells, &wall_cells, behind, 0), style.cell_color(stone, behind, 0), "walls are darker");
+    assert_eq!(style.pixel_color(&grid_cells, &wall_cells, bare, 0), style.cell_color(Elem::new(ElemKind::Empty, false), bare, 0));
+}
+
+#[test]
+fn walls_survive_the_world_file_format() {
+    let mut wall_cells = WallCells::default();
+    wall_cells.set_kind_at(ElemPos::new(0, 0), ElemKind::Stone);
+    wall_cells.set_kind_at(ElemPos::new(9, 7), SAND);
+
+    let copy = WallCells::from_grid_cells(&wall_cells.to_grid_cells());
+    for (x, y) in [(0, 0), (9, 7), (1, 0), (100, 100)] {
+        assert_eq!(copy.get_kind_at(ElemPos::new(x, y)), wall_cells.get_kind_at(ElemPos::new(x, y)));
+    }
+}
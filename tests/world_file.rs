@@ -0,0 +1,167 @@
+//! World files: grids, the colour seeds of their particles and walls survive a round trip, files written before
+//! the seeds or the wall were stored still load, grids load into grids of another size, and damaged or foreign
+//! files are rejected with the matching error instead of panicking or loading a partly filled grid
+
+use sandfall_mimimi::game::sandworld::{wall_layer::WallCells, world_file::{decode_world, encode_world, encode_world_with_wall, WorldFileError, WORLD_FILE_VERSION}, Elem, ElemKind, ElemPos, GridCells, GridSize, SandColor, GRID_SIZE};
+
+/// Offset of the version in the header, right after the magic bytes
+const VERSION_OFFSET: usize = 4;
+/// Offset of the run count, the last field of the header
+const RUN_COUNT_OFFSET: usize = 30;
+/// Length of the header, the runs follow it
+const HEADER_LEN: usize = RUN_COUNT_OFFSET + 4;
+
+fn sample_grid() -> GridCells {
+    let mut grid_cells = GridCells::new_empty();
+    for x in 0..GRID_SIZE.width {
+        grid_cells.set_elem_at(ElemPos::new(x, GRID_SIZE.height - 1), Elem::new(ElemKind::Stone, false));
+    }
+    for (i, sand) in [SandColor::Yellow, SandColor::Red, SandColor::Blue, SandColor::Green].into_iter().enumerate() {
+        let pos = ElemPos::new(10 + i as u32, 20);
+        grid_cells.set_elem_at(pos, Elem::new(ElemKind::Sand(sand), i % 2 == 0).seeded_at_tick(pos, 99));
+    }
+    grid_cells
+}
+
+/// A file with a valid header for the current grid followed by the given runs of empty cells, so no seeds, and no wall
+fn with_runs(runs: &[(u16, u8)]) -> Vec<u8> {
+    let mut bytes = encode_world(&GridCells::new_empty(), 0, 0);
+    bytes.truncate(RUN_COUNT_OFFSET);
+    bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+    for (len, cell) in runs {
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.push(*cell);
+    }
+    bytes.push(0);
+    bytes
+}
+
+#[test]
+fn worlds_survive_a_round_trip() {
+    let grid_cells = sample_grid();
+    let world = decode_world(&encode_world(&grid_cells, 42, 1234)).unwrap();
+
+    assert_eq!((world.meta.seed, world.meta.tick), (42, 1234));
+    assert!(world.fits_grid(GRID_SIZE));
+    let loaded = world.to_grid_cells(GRID_SIZE);
+    for (before, after) in grid_cells.cells.iter().zip(&loaded.cells) {
+        assert_eq!((before.kind(), before.seed()), (after.kind(), after.seed()));
+    }
+}
+
+#[test]
+fn version_1_files_seed_particles_by_position() {
+    let bytes = encode_world(&sample_grid(), 0, 0);
+    let run_count = u32::from_le_bytes(bytes[RUN_COUNT_OFFSET..HEADER_LEN].try_into().unwrap()) as usize;
+    // Version 1 ended after the runs, without the seeds and the wall flag
+    let mut v1 = bytes[..HEADER_LEN + run_count * 3].to_vec();
+    v1[VERSION_OFFSET..VERSION_OFFSET + 2].copy_from_slice(&1u16.to_le_bytes());
+
+    let loaded = decode_world(&v1).unwrap().to_grid_cells(GRID_SIZE);
+    let pos = ElemPos::new(11, 20);
+    let elem = loaded.get_elem_at(pos).unwrap();
+    assert_eq!(elem.kind(), ElemKind::Sand(SandColor::Red));
+    assert_eq!(elem.seed(), elem.seeded_at(pos).seed());
+}
+
+#[test]
+fn walls_survive_a_round_trip() {
+    let mut wall_cells = WallCells::default();
+    wall_cells.set_kind_at(ElemPos::new(3, 4), ElemKind::Stone);
+    wall_cells.set_kind_at(ElemPos::new(200, 150), ElemKind::Sand(SandColor::Blue));
+
+    let world = decode_world(&encode_world_with_wall(&sample_grid(), &wall_cells, 0, 0)).unwrap();
+    let loaded = world.wall_cells(GRID_SIZE);
+    for pos in GRID_SIZE.positions() {
+        assert_eq!(loaded.get_kind_at(pos), wall_cells.get_kind_at(pos), "wall at {pos:?}");
+    }
+    assert_eq!(world.to_grid_cells(GRID_SIZE).get_elem_at(ElemPos::new(10, 20)).unwrap().kind(), ElemKind::Sand(SandColor::Yellow));
+
+    let bare = decode_world(&encode_world(&sample_grid(), 0, 0)).unwrap();
+    assert!(bare.wall.is_none());
+    assert_eq!(bare.wall_cells(GRID_SIZE).get_kind_at(ElemPos::new(3, 4)), Some(ElemKind::Empty));
+}
+
+#[test]
+fn version_2_files_load_with_a_bare_wall() {
+    let mut bytes = encode_world(&sample_grid(), 0, 0);
+    // Version 2 ended after the seeds, without the wall flag
+    bytes.pop();
+    bytes[VERSION_OFFSET..VERSION_OFFSET + 2].copy_from_slice(&2u16.to_le_bytes());
+
+    let world = decode_world(&bytes).unwrap();
+    assert!(world.wall.is_none());
+    assert_eq!(world.to_grid_cells(GRID_SIZE).get_elem_at(ElemPos::new(11, 20)).unwrap().kind(), ElemKind::Sand(SandColor::Red));
+}
+
+#[test]
+fn unknown_wall_flags_are_rejected() {
+    let mut bytes = encode_world(&sample_grid(), 0, 0);
+    *bytes.last_mut().unwrap() = 2;
+    assert!(matches!(decode_world(&bytes), Err(WorldFileError::InvalidWallFlag(2))));
+}
+
+#[test]
+fn worlds_load_into_grids_of_another_size() {
+    let small = GridSize::ALL[0];
+    let corner = ElemPos::new(small.width - 1, small.height - 1);
+    let mut grid_cells = GridCells::new_sized(small);
+    grid_cells.set_elem_at(corner, Elem::new(ElemKind::Stone, false));
+
+    let world = decode_world(&encode_world(&grid_cells, 0, 0)).unwrap();
+    assert_eq!((world.meta.width, world.meta.height), (small.width, small.height));
+    assert!(!world.fits_grid(GRID_SIZE));
+    let padded = world.to_grid_cells(GRID_SIZE);
+    assert_eq!(padded.size(), GRID_SIZE);
+    assert_eq!(padded.get_elem_at(corner).unwrap().kind(), ElemKind::Stone);
+    assert_eq!(padded.get_elem_at(ElemPos::new(small.width, 0)).unwrap().kind(), ElemKind::Empty);
+
+    let cropped = decode_world(&encode_world(&sample_grid(), 0, 0)).unwrap().to_grid_cells(small);
+    assert_eq!(cropped.get_elem_at(ElemPos::new(10, 20)).unwrap().kind(), ElemKind::Sand(SandColor::Yellow));
+    assert!(cropped.get_elem_at(ElemPos::new(small.width, 0)).is_none());
+}
+
+#[test]
+fn foreign_files_are_not_world_files() {
+    assert!(matches!(decode_world(b"PNG\x0d\x0a"), Err(WorldFileError::NotAWorldFile)));
+    assert!(matches!(decode_world(b"SN"), Err(WorldFileError::NotAWorldFile)));
+    assert!(matches!(decode_world(&[]), Err(WorldFileError::NotAWorldFile)));
+}
+
+#[test]
+fn newer_versions_are_rejected() {
+    let mut bytes = encode_world(&sample_grid(), 0, 0);
+    bytes[VERSION_OFFSET..VERSION_OFFSET + 2].copy_from_slice(&(WORLD_FILE_VERSION + 1).to_le_bytes());
+    assert!(matches!(decode_world(&bytes), Err(WorldFileError::UnsupportedVersion(version)) if version == WORLD_FILE_VERSION + 1));
+}
+
+#[test]
+fn truncated_files_are_rejected_at_every_length() {
+    let bytes = encode_world(&sample_grid(), 7, 7);
+    for len in VERSION_OFFSET..bytes.len() {
+        assert!(matches!(decode_world(&bytes[..len]), Err(WorldFileError::Truncated)), "cut after {len} bytes");
+    }
+}
+
+#[test]
+fn runs_must_fill_the_grid_exactly() {
+    let count = (GRID_SIZE.width * GRID_SIZE.height) as usize;
+    let full_runs = (count / u16::MAX as usize) as u16;
+    let rest = (count % u16::MAX as usize) as u16;
+    let mut runs = vec![(u16::MAX, 0); full_runs as usize];
+
+    let short = decode_world(&with_runs(&runs));
+    assert!(matches!(short, Err(WorldFileError::CellCountMismatch { expected, found }) if expected == count && found < count));
+
+    runs.push((rest + 1, 0));
+    let long = decode_world(&with_runs(&runs));
+    assert!(matches!(long, Err(WorldFileError::CellCountMismatch { expected, found }) if expected == count && found == count + 1));
+
+    runs.last_mut().unwrap().0 = rest;
+    assert!(decode_world(&with_runs(&runs)).is_ok());
+}
+
+#[test]
+fn unknown_cells_are_rejected() {
+    assert!(matches!(decode_world(&with_runs(&[(1, 0x7f)])), Err(WorldFileError::InvalidCell(0x7f))));
+}